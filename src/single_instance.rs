@@ -0,0 +1,136 @@
+//! Guards against two Discrakt instances fighting over the same Discord
+//! presence slot, via a pid lock file in the cache dir. Unix uses an
+//! advisory `flock`; other platforms fall back to a stale-pid check, since
+//! there's no cross-platform advisory lock in the standard library.
+
+use std::{fs, path::PathBuf};
+
+use crate::utils::cache_dir_path;
+
+fn lock_file_path() -> PathBuf {
+    cache_dir_path(None).join("discrakt.lock")
+}
+
+/// Holds the single-instance lock for the lifetime of the process; releases
+/// it (best-effort) on drop.
+pub struct InstanceLock {
+    path: PathBuf,
+    #[cfg(unix)]
+    _file: fs::File,
+}
+
+/// Attempts to acquire the single-instance lock, returning `None` if another
+/// live instance already holds it.
+pub fn acquire() -> Option<InstanceLock> {
+    acquire_at(lock_file_path())
+}
+
+/// The path-parameterized half of `acquire`, so the locking behavior can be
+/// tested against a scratch file instead of the real cache dir.
+fn acquire_at(path: PathBuf) -> Option<InstanceLock> {
+    #[cfg(unix)]
+    {
+        acquire_unix(path)
+    }
+    #[cfg(not(unix))]
+    {
+        acquire_fallback(path)
+    }
+}
+
+#[cfg(unix)]
+fn acquire_unix(path: PathBuf) -> Option<InstanceLock> {
+    use std::{io::Write, os::unix::io::AsRawFd};
+
+    extern "C" {
+        fn flock(fd: i32, operation: i32) -> i32;
+    }
+    const LOCK_EX: i32 = 2;
+    const LOCK_NB: i32 = 4;
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&path)
+        .ok()?;
+
+    // SAFETY: `file` is a valid, open fd for the duration of this call.
+    let locked = unsafe { flock(file.as_raw_fd(), LOCK_EX | LOCK_NB) } == 0;
+    if !locked {
+        return None;
+    }
+
+    let _ = write!(file, "{}", std::process::id());
+
+    Some(InstanceLock { path, _file: file })
+}
+
+#[cfg(not(unix))]
+fn acquire_fallback(path: PathBuf) -> Option<InstanceLock> {
+    if let Ok(existing_pid) = fs::read_to_string(&path) {
+        if let Ok(pid) = existing_pid.trim().parse::<u32>() {
+            if pid != std::process::id() && process_is_alive(pid) {
+                return None;
+            }
+        }
+    }
+
+    fs::write(&path, std::process::id().to_string()).ok()?;
+    Some(InstanceLock { path })
+}
+
+#[cfg(not(unix))]
+fn process_is_alive(pid: u32) -> bool {
+    use std::process::Command;
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}")])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+        .unwrap_or(false)
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn scratch_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn acquire_at_succeeds_when_nothing_else_holds_the_lock() {
+        let path = scratch_path("discrakt-test-lock-fresh.lock");
+        assert!(acquire_at(path.clone()).is_some());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn acquire_at_fails_while_another_lock_on_the_same_path_is_held() {
+        let path = scratch_path("discrakt-test-lock-contended.lock");
+        let first = acquire_at(path.clone()).unwrap();
+
+        assert!(acquire_at(path.clone()).is_none());
+
+        drop(first);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn acquire_at_succeeds_again_once_the_prior_lock_is_dropped() {
+        let path = scratch_path("discrakt-test-lock-released.lock");
+        let first = acquire_at(path.clone()).unwrap();
+        drop(first);
+
+        assert!(acquire_at(path.clone()).is_some());
+        let _ = fs::remove_file(&path);
+    }
+}
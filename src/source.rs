@@ -0,0 +1,209 @@
+//! Where discrakt gets "what's currently playing" from. `Trakt` is the default
+//! source; `FileSource` lets power users drive presence from an external
+//! script by writing a JSON file in `TraktWatchingResponse`'s shape instead.
+
+use std::{fs, path::PathBuf, time::SystemTime};
+use ureq::serde_json;
+
+use crate::trakt::{Trakt, TraktWatchingResponse};
+
+pub trait WatchingSource {
+    fn get_watching(&mut self) -> Option<TraktWatchingResponse>;
+}
+
+/// Polls a JSON file for the current watching item, re-reading it only when
+/// its mtime changes so a stalled polling loop doesn't hammer the disk.
+pub struct FileSource {
+    path: PathBuf,
+    last_mtime: Option<SystemTime>,
+    cached: Option<TraktWatchingResponse>,
+}
+
+impl FileSource {
+    pub fn new(path: PathBuf) -> Self {
+        FileSource {
+            path,
+            last_mtime: None,
+            cached: None,
+        }
+    }
+}
+
+impl WatchingSource for FileSource {
+    fn get_watching(&mut self) -> Option<TraktWatchingResponse> {
+        let mtime = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+        if self.last_mtime != Some(mtime) {
+            self.last_mtime = Some(mtime);
+            let contents = fs::read_to_string(&self.path).ok()?;
+            self.cached = serde_json::from_str(&contents).ok();
+        }
+        self.cached.clone()
+    }
+}
+
+/// Polls each override source in priority order (first configured, highest
+/// priority) before falling back to Trakt, so a higher-priority live source
+/// can preempt Trakt polling until it stops reporting anything (e.g. a
+/// `FileSource` fed by an external script). `FileSource` is the only
+/// built-in override today; any other `WatchingSource` plugs in the same way.
+pub struct SourceManager {
+    overrides: Vec<Box<dyn WatchingSource>>,
+}
+
+impl SourceManager {
+    pub fn new(overrides: Vec<Box<dyn WatchingSource>>) -> Self {
+        SourceManager { overrides }
+    }
+
+    fn get_watching(&mut self, trakt: &mut Trakt) -> Option<TraktWatchingResponse> {
+        self.overrides
+            .iter_mut()
+            .find_map(|source| source.get_watching())
+            .or_else(|| WatchingSource::get_watching(trakt))
+    }
+}
+
+/// Selects the active source for "what's currently playing". `Trakt` and
+/// `File` are exclusive, dynamic-dispatch-free choices for the common case of
+/// a single configured source; `Combined` layers override sources on top of
+/// Trakt via a `SourceManager` for `source = combined`.
+pub enum Source {
+    Trakt,
+    File(Box<FileSource>),
+    Combined(SourceManager),
+}
+
+impl Source {
+    pub fn get_watching(&mut self, trakt: &mut Trakt) -> Option<TraktWatchingResponse> {
+        match self {
+            Source::Trakt => WatchingSource::get_watching(trakt),
+            Source::File(file_source) => file_source.get_watching(),
+            Source::Combined(manager) => manager.get_watching(trakt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::ArtworkPreference;
+
+    struct MockSource {
+        response: Option<TraktWatchingResponse>,
+    }
+
+    impl WatchingSource for MockSource {
+        fn get_watching(&mut self) -> Option<TraktWatchingResponse> {
+            self.response.clone()
+        }
+    }
+
+    fn watching(started_at: &str) -> TraktWatchingResponse {
+        TraktWatchingResponse {
+            expires_at: None,
+            started_at: started_at.to_string(),
+            action: "watching".to_string(),
+            r#type: "movie".to_string(),
+            movie: None,
+            show: None,
+            episode: None,
+            is_rewatch: None,
+            progress: None,
+        }
+    }
+
+    fn test_trakt() -> Trakt {
+        Trakt::new(
+            String::new(),
+            String::new(),
+            None,
+            None,
+            String::new(),
+            None,
+            None,
+            ArtworkPreference::Season,
+            None,
+        )
+    }
+
+    #[test]
+    fn get_watching_prefers_first_configured_override() {
+        let mut manager = SourceManager::new(vec![
+            Box::new(MockSource {
+                response: Some(watching("first")),
+            }),
+            Box::new(MockSource {
+                response: Some(watching("second")),
+            }),
+        ]);
+        let mut trakt = test_trakt();
+
+        let result = manager.get_watching(&mut trakt).unwrap();
+
+        assert_eq!(result.started_at, "first");
+    }
+
+    #[test]
+    fn get_watching_falls_through_to_next_override_when_first_is_empty() {
+        let mut manager = SourceManager::new(vec![
+            Box::new(MockSource { response: None }),
+            Box::new(MockSource {
+                response: Some(watching("second")),
+            }),
+        ]);
+        let mut trakt = test_trakt();
+
+        let result = manager.get_watching(&mut trakt).unwrap();
+
+        assert_eq!(result.started_at, "second");
+    }
+
+    fn scratch_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn file_source_returns_none_when_the_file_does_not_exist() {
+        let path = std::env::temp_dir().join("discrakt-test-source-missing.json");
+        let _ = fs::remove_file(&path);
+        let mut source = FileSource::new(path);
+
+        assert!(source.get_watching().is_none());
+    }
+
+    #[test]
+    fn file_source_reads_a_valid_watching_response() {
+        let path = scratch_file(
+            "discrakt-test-source-valid.json",
+            r#"{"started_at": "2024-01-01T00:00:00Z", "action": "watching", "type": "movie"}"#,
+        );
+        let mut source = FileSource::new(path.clone());
+
+        let result = source.get_watching().unwrap();
+
+        assert_eq!(result.started_at, "2024-01-01T00:00:00Z");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_source_caches_until_the_mtime_changes() {
+        let path = scratch_file(
+            "discrakt-test-source-cache.json",
+            r#"{"started_at": "first", "action": "watching", "type": "movie"}"#,
+        );
+        let mut source = FileSource::new(path.clone());
+        assert_eq!(source.get_watching().unwrap().started_at, "first");
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        fs::write(
+            &path,
+            r#"{"started_at": "second", "action": "watching", "type": "movie"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(source.get_watching().unwrap().started_at, "second");
+        fs::remove_file(&path).unwrap();
+    }
+}
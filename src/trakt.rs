@@ -1,24 +1,38 @@
+use chrono::Utc;
 use serde::Deserialize;
-use std::{collections::HashMap, time::Duration};
-use ureq::{serde_json, Agent, AgentBuilder};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use ureq::{serde_json, Agent};
 
-use crate::utils::{log, MediaType};
+use crate::utils::{build_agent, log, ArtworkPreference, MediaType};
 
-#[derive(Deserialize)]
+/// After this many consecutive TMDB request failures, the circuit opens and
+/// discrakt stops attempting poster/logo calls until `TMDB_CIRCUIT_COOLDOWN`
+/// elapses, avoiding per-poll latency while TMDB is down.
+const TMDB_CIRCUIT_FAILURE_THRESHOLD: u32 = 3;
+const TMDB_CIRCUIT_COOLDOWN: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Deserialize)]
 pub struct TraktMovie {
     pub title: String,
     pub year: u16,
     pub ids: TraktIds,
+    /// Runtime in minutes, used to derive the presence end time when
+    /// `expires_at` is missing (e.g. some third-party `watching` fixtures).
+    #[serde(default)]
+    pub runtime: Option<u32>,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct TraktShow {
     pub title: String,
     pub year: u16,
     pub ids: TraktIds,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct TraktEpisode {
     pub season: u8,
     pub number: u8,
@@ -26,7 +40,7 @@ pub struct TraktEpisode {
     pub ids: TraktIds,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct TraktIds {
     pub trakt: u32,
     pub slug: Option<String>,
@@ -36,15 +50,44 @@ pub struct TraktIds {
     pub tvrage: Option<u32>,
 }
 
-#[derive(Deserialize)]
+impl TraktIds {
+    /// Trakt accepts either a slug or the numeric id in its URLs and API paths,
+    /// so this falls back to the id for slug-less items instead of failing.
+    pub fn slug_or_id(&self) -> String {
+        self.slug.clone().unwrap_or_else(|| self.trakt.to_string())
+    }
+}
+
+#[derive(Clone, Deserialize)]
 pub struct TraktWatchingResponse {
-    pub expires_at: String,
+    /// Absent or empty for some fixtures/sources; when missing, the presence
+    /// end time is derived from `started_at + movie.runtime` instead.
+    #[serde(default)]
+    pub expires_at: Option<String>,
     pub started_at: String,
     pub action: String,
     pub r#type: String,
     pub movie: Option<TraktMovie>,
     pub show: Option<TraktShow>,
     pub episode: Option<TraktEpisode>,
+    /// Set when Trakt reports this as a rewatch. Not always present, since the
+    /// `watching` endpoint doesn't consistently include it for every media type.
+    #[serde(default)]
+    pub is_rewatch: Option<bool>,
+    /// Watch progress (0-100) as reported by Trakt, when present. Preferred over
+    /// deriving it from `started_at`/`expires_at`, since it accounts for pauses.
+    #[serde(default)]
+    pub progress: Option<f32>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct TraktHistoryItem {
+    pub watched_at: String,
+    pub action: String,
+    pub r#type: String,
+    pub movie: Option<TraktMovie>,
+    pub show: Option<TraktShow>,
+    pub episode: Option<TraktEpisode>,
 }
 
 #[derive(Deserialize)]
@@ -54,36 +97,272 @@ pub struct TraktRatingsResponse {
     pub distribution: HashMap<String, u16>,
 }
 
+#[derive(Deserialize)]
+pub struct TraktSyncRatingMovie {
+    pub ids: TraktIds,
+}
+
+#[derive(Deserialize)]
+pub struct TraktSyncRating {
+    pub rating: u8,
+    pub movie: TraktSyncRatingMovie,
+}
+
+pub const DEFAULT_TRAKT_BASE_URL: &str = "https://api.trakt.tv";
+pub const DEFAULT_TMDB_BASE_URL: &str = "https://api.themoviedb.org/3";
+
+/// Falls back to `default` and warns when `url` isn't a valid `http(s)` URL,
+/// so a typo'd `traktBaseUrl`/`tmdbBaseUrl` doesn't silently break all requests.
+fn valid_base_url(url: Option<String>, default: &str, config_key: &str) -> String {
+    match url {
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+            url.trim_end_matches('/').to_string()
+        }
+        Some(url) => {
+            eprintln!(
+                "Configured {config_key} {url:?} is not a valid URL, falling back to default"
+            );
+            default.to_string()
+        }
+        None => default.to_string(),
+    }
+}
+
+/// TMDB v4 read access tokens are JWTs (three dot-separated base64url
+/// segments); v3 tokens are a plain 32-character API key. They authenticate
+/// differently, so this decides whether to send an `Authorization: Bearer`
+/// header instead of the `api_key` query param.
+pub fn is_tmdb_v4_token(token: &str) -> bool {
+    token.split('.').count() == 3 && token.len() > 32
+}
+
 pub struct Trakt {
     rating_cache: HashMap<String, f64>,
+    my_ratings_cache: Option<HashMap<u32, u8>>,
     image_cache: HashMap<String, String>,
+    company_logo_cache: HashMap<String, String>,
+    certification_cache: HashMap<String, String>,
     agent: Agent,
     client_id: String,
     username: String,
     oauth_access_token: Option<String>,
+    extended: String,
+    rate_limited: bool,
+    unreachable: bool,
+    trakt_base_url: String,
+    tmdb_base_url: String,
+    tmdb_consecutive_failures: u32,
+    tmdb_circuit_open_until: Option<Instant>,
+    artwork_preference: ArtworkPreference,
+    poster_size: &'static str,
+}
+
+/// Clamps the requested poster/still size to the largest TMDB size token that
+/// doesn't exceed `max_resolution_px`, since Discord downsamples oversized
+/// images anyway and `original` wastes bandwidth for no visible gain. `None`
+/// keeps the historical default (600px, the size discrakt has always used).
+fn poster_size_token(max_resolution_px: Option<u32>) -> &'static str {
+    match max_resolution_px {
+        None => "w600_and_h600_bestv2",
+        Some(max) if max >= 780 => "w780",
+        Some(max) if max >= 600 => "w600_and_h600_bestv2",
+        Some(max) if max >= 342 => "w342",
+        Some(max) if max >= 185 => "w185",
+        _ => "w92",
+    }
+}
+
+/// Builds the synthetic `watching` response for the `pinItem` debug config
+/// from a TMDB `/movie/{id}` body, pulled out of `get_pinned_watching` so the
+/// construction can be tested without a live TMDB call.
+fn pinned_watching_from_tmdb_movie(
+    body: &serde_json::Value,
+    tmdb_id: &str,
+    started_at: String,
+) -> Option<TraktWatchingResponse> {
+    let title = body["title"].as_str()?.to_string();
+    let year = body["release_date"]
+        .as_str()
+        .and_then(|date| date.get(0..4))
+        .and_then(|year| year.parse().ok())
+        .unwrap_or(0);
+    let runtime = body["runtime"].as_u64().map(|runtime| runtime as u32);
+
+    Some(TraktWatchingResponse {
+        expires_at: None,
+        started_at,
+        action: "watching".to_string(),
+        r#type: "movie".to_string(),
+        movie: Some(TraktMovie {
+            title,
+            year,
+            ids: TraktIds {
+                trakt: 0,
+                slug: None,
+                tvdb: None,
+                imdb: None,
+                tmdb: tmdb_id.parse().ok(),
+                tvrage: None,
+            },
+            runtime,
+        }),
+        show: None,
+        episode: None,
+        is_rewatch: None,
+        progress: None,
+    })
+}
+
+/// Returns the URL of the first image under `array_key` (`"posters"` or
+/// `"stills"`) that has a `file_path`, skipping any null entries TMDB
+/// sometimes includes, pulled out of `fetch_poster` so the picking logic can
+/// be tested without a live TMDB call.
+fn first_image_url(body: &serde_json::Value, array_key: &str, poster_size: &str) -> Option<String> {
+    let empty = Vec::new();
+    let file_path = body[array_key]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .find_map(|image| image.get("file_path")?.as_str());
+
+    file_path.map(|file_path| format!("https://image.tmdb.org/t/p/{poster_size}{file_path}"))
+}
+
+/// Picks the certification for `region` out of a TMDB release-dates/content-
+/// ratings response, pulled out of `get_certification` so the region lookup
+/// and per-media-type shape can be tested without a live TMDB call.
+fn extract_certification(
+    body: &serde_json::Value,
+    media_type: MediaType,
+    region: &str,
+) -> Option<String> {
+    let results = body["results"].as_array()?;
+    let region_entry = results
+        .iter()
+        .find(|entry| entry["iso_3166_1"].as_str() == Some(region))?;
+    let certification = match media_type {
+        MediaType::Movie => region_entry["release_dates"]
+            .as_array()?
+            .iter()
+            .find_map(|release| release["certification"].as_str())?,
+        MediaType::Show => region_entry["rating"].as_str()?,
+    };
+    if certification.is_empty() {
+        return None;
+    }
+    Some(certification.to_string())
+}
+
+/// Snapshot of how many entries each of `Trakt`'s in-memory caches currently holds.
+pub struct CacheStats {
+    pub ratings: usize,
+    pub images: usize,
+    pub company_logos: usize,
+    pub certifications: usize,
 }
 
 impl Trakt {
-    pub fn new(client_id: String, username: String, oauth_access_token: Option<String>) -> Trakt {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        client_id: String,
+        username: String,
+        oauth_access_token: Option<String>,
+        tls_ca_file: Option<String>,
+        extended: String,
+        trakt_base_url: Option<String>,
+        tmdb_base_url: Option<String>,
+        artwork_preference: ArtworkPreference,
+        max_poster_resolution: Option<u32>,
+    ) -> Trakt {
         Trakt {
             rating_cache: HashMap::default(),
+            my_ratings_cache: None,
             image_cache: HashMap::default(),
-            agent: AgentBuilder::new()
-                .timeout_read(Duration::from_secs(5))
-                .timeout_write(Duration::from_secs(5))
-                .build(),
+            company_logo_cache: HashMap::default(),
+            certification_cache: HashMap::default(),
+            agent: build_agent(tls_ca_file.as_deref()),
             client_id,
             username,
             oauth_access_token,
+            extended,
+            rate_limited: false,
+            unreachable: false,
+            trakt_base_url: valid_base_url(trakt_base_url, DEFAULT_TRAKT_BASE_URL, "traktBaseUrl"),
+            tmdb_base_url: valid_base_url(tmdb_base_url, DEFAULT_TMDB_BASE_URL, "tmdbBaseUrl"),
+            tmdb_consecutive_failures: 0,
+            tmdb_circuit_open_until: None,
+            artwork_preference,
+            poster_size: poster_size_token(max_poster_resolution),
         }
     }
 
-    pub fn get_watching(&self) -> Option<TraktWatchingResponse> {
-        let endpoint = format!("https://api.trakt.tv/users/{}/watching", self.username);
+    /// Whether the TMDB circuit breaker is open, meaning discrakt should skip
+    /// poster/logo calls and fall back to media-type images and Trakt titles.
+    fn tmdb_circuit_open(&self) -> bool {
+        self.tmdb_circuit_open_until
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    fn record_tmdb_failure(&mut self) {
+        self.tmdb_consecutive_failures += 1;
+        if self.tmdb_consecutive_failures >= TMDB_CIRCUIT_FAILURE_THRESHOLD {
+            log("TMDB appears to be down, pausing poster/logo lookups for a cooldown");
+            self.tmdb_circuit_open_until = Some(Instant::now() + TMDB_CIRCUIT_COOLDOWN);
+        }
+    }
+
+    fn record_tmdb_success(&mut self) {
+        self.tmdb_consecutive_failures = 0;
+        self.tmdb_circuit_open_until = None;
+    }
+
+    /// Whether the last `get_watching` call was rejected with a 429, meaning
+    /// discrakt is backing off before it can poll again.
+    pub fn is_rate_limited(&self) -> bool {
+        self.rate_limited
+    }
+
+    /// Whether the last `watching` request never reached Trakt at all (a
+    /// transport-level failure), as opposed to a normal "nothing watched"
+    /// response - lets the polling loop log a distinct "Trakt unreachable"
+    /// instead of the misleading "nothing is being played".
+    pub fn is_unreachable(&self) -> bool {
+        self.unreachable
+    }
+
+    /// Updates the OAuth access token used for subsequent requests, so the
+    /// polling loop can push a refreshed token without rebuilding the client.
+    pub fn set_oauth_access_token(&mut self, oauth_access_token: Option<String>) {
+        self.oauth_access_token = oauth_access_token;
+    }
+
+    pub fn get_watching(&mut self) -> Option<TraktWatchingResponse> {
+        let username = self.username.clone();
+        self.get_watching_as(&username)
+    }
+
+    /// Like `get_watching`, but for an arbitrary username instead of the
+    /// configured one, for "family mode" polling of `additionalUsers`.
+    pub fn get_watching_as(&mut self, username: &str) -> Option<TraktWatchingResponse> {
+        self.get_watching_raw(username).0
+    }
+
+    /// Like `get_watching_as`, but also returns the raw HTTP status so callers
+    /// can distinguish 200 (watching), 204 (nothing watched), 401
+    /// (unauthorized), and 429 (rate limited) instead of collapsing them all
+    /// into `None`. A status of `0` means the request never reached Trakt
+    /// (a transport-level failure, e.g. DNS/connection error). Untested - the
+    /// 204-vs-parse-failure distinction lives inline in a live network call,
+    /// with no `ureq::Response` construction available outside one to
+    /// exercise it against, and this crate has no `mockito` (or similar)
+    /// dependency to stand up a fake Trakt server against.
+    pub fn get_watching_raw(&mut self, username: &str) -> (Option<TraktWatchingResponse>, u16) {
+        let endpoint = format!("{}/users/{username}/watching", self.trakt_base_url);
 
         let request = self
             .agent
             .get(&endpoint)
+            .query("extended", &self.extended)
             .set("Content-Type", "application/json")
             .set("trakt-api-version", "2")
             .set("trakt-api-key", &self.client_id);
@@ -99,94 +378,643 @@ impl Trakt {
 
         let response = match request.call() {
             Ok(response) => response,
-            Err(_) => return None,
+            Err(ureq::Error::Status(code, _)) => {
+                self.rate_limited = code == 429;
+                self.unreachable = false;
+                return (None, code);
+            }
+            Err(ureq::Error::Transport(_)) => {
+                self.unreachable = true;
+                return (None, 0);
+            }
         };
 
-        response.into_json().unwrap_or_default()
+        self.rate_limited = false;
+        self.unreachable = false;
+        let status = response.status();
+        if status == 204 {
+            // Nothing is being watched; this is expected, not a parse failure.
+            return (None, status);
+        }
+
+        match response.into_json() {
+            Ok(watching) => (Some(watching), status),
+            Err(e) => {
+                log(&format!("Failed to parse watching response: {e}"));
+                (None, status)
+            }
+        }
+    }
+
+    /// Builds a synthetic `watching` response for the `pinItem` debug config
+    /// (pinning presence to a specific title regardless of what's actually
+    /// playing on Trakt, for testing presence rendering), by looking up the
+    /// title/year/runtime from TMDB. Only movies (`tmdb:<id>`) are supported,
+    /// since that covers what `pinItem` is for.
+    pub fn get_pinned_watching(
+        &self,
+        tmdb_id: &str,
+        tmdb_token: &str,
+    ) -> Option<TraktWatchingResponse> {
+        let response = self
+            .tmdb_request(&format!("/movie/{tmdb_id}"), tmdb_token)
+            .call()
+            .ok()?;
+        let body: serde_json::Value = response.into_json().ok()?;
+        pinned_watching_from_tmdb_movie(&body, tmdb_id, Utc::now().to_rfc3339())
+    }
+
+    /// Reports the current size of each in-memory cache, for periodic diagnostics.
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            ratings: self.rating_cache.len(),
+            images: self.image_cache.len(),
+            company_logos: self.company_logo_cache.len(),
+            certifications: self.certification_cache.len(),
+        }
     }
 
+    /// Builds a TMDB request for `path` (e.g. `/movie/603/images`), authenticated
+    /// with either a v3 `api_key` query param or, for v4 read access tokens
+    /// (JWTs), an `Authorization: Bearer` header.
+    fn tmdb_request(&self, path: &str, tmdb_token: &str) -> ureq::Request {
+        let endpoint = format!("{}{path}", self.tmdb_base_url);
+        if is_tmdb_v4_token(tmdb_token) {
+            self.agent
+                .get(&endpoint)
+                .set("Authorization", &format!("Bearer {tmdb_token}"))
+        } else {
+            self.agent.get(&endpoint).query("api_key", tmdb_token)
+        }
+    }
+
+    /// Makes a cheap TMDB call to verify the configured token is still valid.
+    /// Untested here, same as the rest of this file's `ureq`-calling methods -
+    /// it's a thin wrapper around a live network call built by `tmdb_request`.
+    pub fn check_tmdb_token(&self, tmdb_token: &str) -> bool {
+        matches!(self.tmdb_request("/configuration", tmdb_token).call(), Ok(response) if response.status() == 200)
+    }
+
+    /// Fetches a TMDB `images` endpoint and returns the first image's URL from
+    /// the given array key (`"posters"` or `"stills"`), if any. `Err` means the
+    /// request itself failed (network/TMDB down), as opposed to a successful
+    /// response with no images.
+    fn fetch_poster(
+        &self,
+        path: &str,
+        tmdb_token: &str,
+        array_key: &str,
+    ) -> Result<Option<String>, ()> {
+        let response = self.tmdb_request(path, tmdb_token).call().map_err(|_| ())?;
+        let body: serde_json::Value = response.into_json().map_err(|_| ())?;
+        Ok(first_image_url(&body, array_key, self.poster_size))
+    }
+
+    /// Picks the poster/still for a title, falling back from episode still ->
+    /// season poster -> show poster (in `Still` mode), season -> show (in
+    /// `Season` mode), or the show poster alone (in `Show` mode), since many
+    /// seasons/episodes have no artwork of their own.
+    /// Untested - each step is a live `fetch_poster` network call, and the
+    /// fallback chain itself has no pure logic beyond `first_image_url`
+    /// (covered separately) once those calls are made.
     pub fn get_poster(
         &mut self,
         media_type: MediaType,
         tmdb_id: String,
         tmdb_token: String,
         season_id: u8,
+        episode_number: Option<u8>,
     ) -> Option<String> {
-        match self.image_cache.get(&tmdb_id) {
-            Some(image_url) => Some(image_url.to_string()),
-            None => {
-                let endpoint = match media_type {
-                    MediaType::Movie => format!("https://api.themoviedb.org/3/movie/{tmdb_id}/images?api_key={tmdb_token}"),
-                    MediaType::Show => format!("https://api.themoviedb.org/3/tv/{tmdb_id}/season/{season_id}/images?api_key={tmdb_token}")
-                };
-
-                let response = self.agent.get(&endpoint).call();
-
-                if response.is_err() {
-                    log(&format!(
-                        "{} image not correctly found",
-                        media_type.as_str()
-                    ));
-                    return None;
-                }
+        if let Some(image_url) = self.image_cache.get(&tmdb_id) {
+            return Some(image_url.to_string());
+        }
+
+        if self.tmdb_circuit_open() {
+            return None;
+        }
+
+        let poster = match media_type {
+            MediaType::Movie => {
+                let path = format!("/movie/{tmdb_id}/images");
+                self.fetch_poster(&path, &tmdb_token, "posters")
+            }
+            MediaType::Show => {
+                let show_path = format!("/tv/{tmdb_id}/images");
+                let season_path = format!("/tv/{tmdb_id}/season/{season_id}/images");
 
-                match response.unwrap().into_json::<serde_json::Value>() {
-                    Ok(body) => {
-                        if body["posters"].as_array().unwrap_or(&vec![]).is_empty() {
-                            log("Show image not correctly found");
-                            return None;
-                        }
-
-                        let image_url = format!(
-                            "https://image.tmdb.org/t/p/w600_and_h600_bestv2{}",
-                            body["posters"][0]
-                                .clone()
-                                .get("file_path")
-                                .unwrap()
-                                .as_str()
-                                .unwrap()
-                        );
-                        Some(image_url)
+                if self.artwork_preference == ArtworkPreference::Show {
+                    self.fetch_poster(&show_path, &tmdb_token, "posters")
+                } else if self.artwork_preference == ArtworkPreference::Still
+                    && episode_number.is_some()
+                {
+                    let episode_path = format!(
+                        "/tv/{tmdb_id}/season/{season_id}/episode/{}/images",
+                        episode_number.unwrap()
+                    );
+                    match self.fetch_poster(&episode_path, &tmdb_token, "stills") {
+                        Ok(None) => match self.fetch_poster(&season_path, &tmdb_token, "posters") {
+                            Ok(None) => self.fetch_poster(&show_path, &tmdb_token, "posters"),
+                            result => result,
+                        },
+                        result => result,
                     }
-                    Err(_) => {
-                        log(&format!(
-                            "{} image not correctly found",
-                            media_type.as_str()
-                        ));
-                        None
+                } else {
+                    match self.fetch_poster(&season_path, &tmdb_token, "posters") {
+                        // Some seasons have no posters of their own; fall back to the show's.
+                        Ok(None) => self.fetch_poster(&show_path, &tmdb_token, "posters"),
+                        result => result,
                     }
                 }
             }
+        };
+
+        match poster {
+            Ok(Some(poster)) => {
+                self.record_tmdb_success();
+                self.image_cache.insert(tmdb_id, poster.clone());
+                Some(poster)
+            }
+            Ok(None) => {
+                self.record_tmdb_success();
+                log(&format!(
+                    "{} image not correctly found",
+                    media_type.as_str()
+                ));
+                None
+            }
+            Err(()) => {
+                self.record_tmdb_failure();
+                log(&format!(
+                    "{} image not correctly found",
+                    media_type.as_str()
+                ));
+                None
+            }
         }
     }
 
+    /// Returns the authenticated user's own rating for a movie (1-10), fetching and
+    /// caching the full `/sync/ratings/movies` list on first use. Requires OAuth;
+    /// returns `None` when unauthenticated or the movie hasn't been rated.
+    pub fn get_my_rating(&mut self, trakt_id: u32) -> Option<u8> {
+        let access_token = self.oauth_access_token.as_ref()?;
+        if self.my_ratings_cache.is_none() {
+            let endpoint = format!("{}/sync/ratings/movies", self.trakt_base_url);
+            let response = self
+                .agent
+                .get(&endpoint)
+                .set("Content-Type", "application/json")
+                .set("trakt-api-version", "2")
+                .set("trakt-api-key", &self.client_id)
+                .set("Authorization", &format!("Bearer {access_token}"))
+                .call()
+                .ok()?;
+
+            let ratings: Vec<TraktSyncRating> = response.into_json().ok()?;
+            let by_id = ratings
+                .into_iter()
+                .map(|r| (r.movie.ids.trakt, r.rating))
+                .collect();
+            self.my_ratings_cache = Some(by_id);
+        }
+
+        self.my_ratings_cache.as_ref()?.get(&trakt_id).copied()
+    }
+
+    /// Returns the user's `limit` most recently watched items from
+    /// `/sync/history`, newest first, for a startup-only "just finished"
+    /// presence when nothing is currently watching. Requires OAuth; returns
+    /// `None` when unauthenticated or the request fails. Not cached, since
+    /// this is only ever called once, at startup.
+    pub fn get_recent_history(&self, limit: u32) -> Option<Vec<TraktHistoryItem>> {
+        let access_token = self.oauth_access_token.as_ref()?;
+        let endpoint = format!("{}/sync/history", self.trakt_base_url);
+        let response = self
+            .agent
+            .get(&endpoint)
+            .query("limit", &limit.to_string())
+            .set("Content-Type", "application/json")
+            .set("trakt-api-version", "2")
+            .set("trakt-api-key", &self.client_id)
+            .set("Authorization", &format!("Bearer {access_token}"))
+            .call()
+            .ok()?;
+
+        response.into_json().ok()
+    }
+
+    /// Looks up the production company logo for a title, for use as the small image
+    /// instead of the generic "trakt" asset.
+    pub fn get_company_logo(
+        &mut self,
+        media_type: MediaType,
+        tmdb_id: String,
+        tmdb_token: String,
+    ) -> Option<String> {
+        if let Some(logo) = self.company_logo_cache.get(&tmdb_id) {
+            return Some(logo.to_string());
+        }
+
+        if self.tmdb_circuit_open() {
+            return None;
+        }
+
+        let path = match media_type {
+            MediaType::Movie => format!("/movie/{tmdb_id}"),
+            MediaType::Show => format!("/tv/{tmdb_id}"),
+        };
+
+        let response = match self.tmdb_request(&path, &tmdb_token).call() {
+            Ok(response) => response,
+            Err(_) => {
+                self.record_tmdb_failure();
+                return None;
+            }
+        };
+        let body: serde_json::Value = response.into_json().ok()?;
+        self.record_tmdb_success();
+        let logo_path = body["production_companies"][0]["logo_path"].as_str()?;
+        let logo_url = format!("https://image.tmdb.org/t/p/w300{logo_path}");
+        self.company_logo_cache
+            .insert(tmdb_id, logo_url.to_string());
+        Some(logo_url)
+    }
+
+    /// Looks up the region-specific content certification (e.g. "PG-13",
+    /// "TV-14") for a title, for use as the small text instead of the generic
+    /// configured value. Cached per `tmdb_id`+`region`, since a title's
+    /// certification doesn't change between polls.
+    pub fn get_certification(
+        &mut self,
+        media_type: MediaType,
+        tmdb_id: String,
+        tmdb_token: String,
+        region: &str,
+    ) -> Option<String> {
+        let cache_key = format!("{tmdb_id}-{region}");
+        if let Some(certification) = self.certification_cache.get(&cache_key) {
+            return Some(certification.to_string());
+        }
+
+        if self.tmdb_circuit_open() {
+            return None;
+        }
+
+        let path = match media_type {
+            MediaType::Movie => format!("/movie/{tmdb_id}/release_dates"),
+            MediaType::Show => format!("/tv/{tmdb_id}/content_ratings"),
+        };
+
+        let response = match self.tmdb_request(&path, &tmdb_token).call() {
+            Ok(response) => response,
+            Err(_) => {
+                self.record_tmdb_failure();
+                return None;
+            }
+        };
+        let body: serde_json::Value = response.into_json().ok()?;
+        self.record_tmdb_success();
+
+        let certification = extract_certification(&body, media_type, region)?;
+        self.certification_cache
+            .insert(cache_key, certification.clone());
+        Some(certification)
+    }
+
     pub fn get_movie_rating(&mut self, movie_slug: String) -> f64 {
         match self.rating_cache.get(&movie_slug) {
             Some(rating) => *rating,
-            None => {
-                let endpoint = format!("https://api.trakt.tv/movies/{movie_slug}/ratings");
-
-                let response = match self
-                    .agent
-                    .get(&endpoint)
-                    .set("Content-Type", "application/json")
-                    .set("trakt-api-version", "2")
-                    .set("trakt-api-key", &self.client_id)
-                    .call()
+            None => match self.get_movie_rating_full(movie_slug) {
+                Some(body) => body.rating,
+                None => 0.0,
+            },
+        }
+    }
+
+    /// Like `get_movie_rating`, but keeps the vote count and distribution instead of
+    /// discarding them. Untested, like `get_movie_rating` itself - it's a live
+    /// network call with no pure logic beyond inserting into `rating_cache`.
+    pub fn get_movie_rating_full(&mut self, movie_slug: String) -> Option<TraktRatingsResponse> {
+        let endpoint = format!("{}/movies/{movie_slug}/ratings", self.trakt_base_url);
+
+        let response = self
+            .agent
+            .get(&endpoint)
+            .set("Content-Type", "application/json")
+            .set("trakt-api-version", "2")
+            .set("trakt-api-key", &self.client_id)
+            .call()
+            .ok()?;
+
+        let body: TraktRatingsResponse = response.into_json().ok()?;
+        self.rating_cache.insert(movie_slug, body.rating);
+        Some(body)
+    }
+}
+
+impl crate::source::WatchingSource for Trakt {
+    fn get_watching(&mut self) -> Option<TraktWatchingResponse> {
+        Trakt::get_watching(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthesizes_a_watching_response_from_a_tmdb_movie_body() {
+        let body = serde_json::json!({
+            "title": "The Matrix",
+            "release_date": "1999-03-30",
+            "runtime": 136,
+        });
+
+        let response =
+            pinned_watching_from_tmdb_movie(&body, "603", "2024-01-01T00:00:00Z".to_string())
+                .unwrap();
+
+        assert_eq!(response.action, "watching");
+        assert_eq!(response.r#type, "movie");
+        assert_eq!(response.started_at, "2024-01-01T00:00:00Z");
+        let movie = response.movie.unwrap();
+        assert_eq!(movie.title, "The Matrix");
+        assert_eq!(movie.year, 1999);
+        assert_eq!(movie.runtime, Some(136));
+        assert_eq!(movie.ids.tmdb, Some(603));
+    }
+
+    #[test]
+    fn returns_none_when_the_tmdb_body_has_no_title() {
+        let body = serde_json::json!({ "release_date": "1999-03-30" });
+
+        assert!(
+            pinned_watching_from_tmdb_movie(&body, "603", "2024-01-01T00:00:00Z".to_string())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn valid_base_url_accepts_a_configured_https_mirror_and_trims_a_trailing_slash() {
+        let url = valid_base_url(
+            Some("https://trakt.example.com/".to_string()),
+            "https://api.trakt.tv",
+            "traktBaseUrl",
+        );
+
+        assert_eq!(url, "https://trakt.example.com");
+    }
+
+    #[test]
+    fn valid_base_url_falls_back_to_the_default_for_an_invalid_scheme() {
+        let url = valid_base_url(
+            Some("ftp://trakt.example.com".to_string()),
+            "https://api.trakt.tv",
+            "traktBaseUrl",
+        );
+
+        assert_eq!(url, "https://api.trakt.tv");
+    }
+
+    #[test]
+    fn valid_base_url_falls_back_to_the_default_when_unconfigured() {
+        let url = valid_base_url(None, "https://api.trakt.tv", "traktBaseUrl");
+
+        assert_eq!(url, "https://api.trakt.tv");
+    }
+
+    #[test]
+    fn poster_size_token_keeps_the_historical_default_when_unconfigured() {
+        assert_eq!(poster_size_token(None), "w600_and_h600_bestv2");
+    }
+
+    #[test]
+    fn slug_or_id_prefers_the_slug_when_present() {
+        let ids = TraktIds {
+            trakt: 603,
+            slug: Some("the-matrix-1999".to_string()),
+            tvdb: None,
+            imdb: None,
+            tmdb: None,
+            tvrage: None,
+        };
+
+        assert_eq!(ids.slug_or_id(), "the-matrix-1999");
+    }
+
+    #[test]
+    fn slug_or_id_falls_back_to_the_numeric_id_when_slug_is_missing() {
+        let ids = TraktIds {
+            trakt: 603,
+            slug: None,
+            tvdb: None,
+            imdb: None,
+            tmdb: None,
+            tvrage: None,
+        };
+
+        assert_eq!(ids.slug_or_id(), "603");
+    }
+
+    #[test]
+    fn is_tmdb_v4_token_recognizes_a_three_segment_jwt() {
+        let v4_token = format!("{}.{}.{}", "a".repeat(20), "b".repeat(20), "c".repeat(20));
+        assert!(is_tmdb_v4_token(&v4_token));
+    }
+
+    #[test]
+    fn is_tmdb_v4_token_rejects_a_plain_v3_api_key() {
+        assert!(!is_tmdb_v4_token(&"a".repeat(32)));
+    }
+
+    #[test]
+    fn poster_size_token_clamps_down_to_the_largest_size_that_fits() {
+        assert_eq!(poster_size_token(Some(1000)), "w780");
+        assert_eq!(poster_size_token(Some(700)), "w600_and_h600_bestv2");
+        assert_eq!(poster_size_token(Some(400)), "w342");
+        assert_eq!(poster_size_token(Some(200)), "w185");
+        assert_eq!(poster_size_token(Some(50)), "w92");
+    }
+
+    fn test_trakt() -> Trakt {
+        Trakt::new(
+            "client-id".to_string(),
+            "user".to_string(),
+            None,
+            None,
+            "full".to_string(),
+            None,
+            None,
+            ArtworkPreference::Season,
+            None,
+        )
+    }
+
+    #[test]
+    fn tmdb_circuit_stays_closed_below_the_failure_threshold() {
+        let mut trakt = test_trakt();
+        trakt.record_tmdb_failure();
+        trakt.record_tmdb_failure();
+
+        assert!(!trakt.tmdb_circuit_open());
+    }
+
+    #[test]
+    fn tmdb_circuit_opens_at_the_failure_threshold() {
+        let mut trakt = test_trakt();
+        trakt.record_tmdb_failure();
+        trakt.record_tmdb_failure();
+        trakt.record_tmdb_failure();
+
+        assert!(trakt.tmdb_circuit_open());
+    }
+
+    #[test]
+    fn tmdb_circuit_closes_again_on_success() {
+        let mut trakt = test_trakt();
+        trakt.record_tmdb_failure();
+        trakt.record_tmdb_failure();
+        trakt.record_tmdb_failure();
+        assert!(trakt.tmdb_circuit_open());
+
+        trakt.record_tmdb_success();
+
+        assert!(!trakt.tmdb_circuit_open());
+        assert_eq!(trakt.tmdb_consecutive_failures, 0);
+    }
+
+    #[test]
+    fn is_rate_limited_reflects_the_rate_limited_flag() {
+        let mut trakt = test_trakt();
+        assert!(!trakt.is_rate_limited());
+
+        trakt.rate_limited = true;
+        assert!(trakt.is_rate_limited());
+    }
+
+    #[test]
+    fn is_unreachable_reflects_the_unreachable_flag() {
+        let mut trakt = test_trakt();
+        assert!(!trakt.is_unreachable());
+
+        trakt.unreachable = true;
+        assert!(trakt.is_unreachable());
+    }
+
+    #[test]
+    fn first_image_url_skips_a_null_entry_and_picks_the_first_with_a_file_path() {
+        let body = serde_json::json!({
+            "posters": [
+                {"file_path": null},
+                {"file_path": "/abc.jpg"},
+                {"file_path": "/def.jpg"},
+            ]
+        });
+
+        assert_eq!(
+            first_image_url(&body, "posters", "w600_and_h600_bestv2"),
+            Some("https://image.tmdb.org/t/p/w600_and_h600_bestv2/abc.jpg".to_string())
+        );
+    }
+
+    #[test]
+    fn first_image_url_returns_none_when_the_array_is_empty() {
+        let body = serde_json::json!({"posters": []});
+        assert_eq!(
+            first_image_url(&body, "posters", "w600_and_h600_bestv2"),
+            None
+        );
+    }
+
+    #[test]
+    fn extract_certification_reads_a_movies_release_dates_response() {
+        let body = serde_json::json!({
+            "results": [
                 {
-                    Ok(response) => response,
-                    Err(_) => return 0.0,
-                };
-
-                match response.into_json::<TraktRatingsResponse>() {
-                    Ok(body) => {
-                        self.rating_cache
-                            .insert(movie_slug.to_string(), body.rating);
-                        body.rating
-                    }
-                    Err(_) => 0.0,
+                    "iso_3166_1": "US",
+                    "release_dates": [{"certification": "PG-13"}]
                 }
-            }
-        }
+            ]
+        });
+        assert_eq!(
+            extract_certification(&body, MediaType::Movie, "US"),
+            Some("PG-13".to_string())
+        );
+    }
+
+    #[test]
+    fn extract_certification_reads_a_shows_content_ratings_response() {
+        let body = serde_json::json!({
+            "results": [{"iso_3166_1": "US", "rating": "TV-14"}]
+        });
+        assert_eq!(
+            extract_certification(&body, MediaType::Show, "US"),
+            Some("TV-14".to_string())
+        );
+    }
+
+    #[test]
+    fn extract_certification_returns_none_when_the_region_is_missing() {
+        let body = serde_json::json!({
+            "results": [{"iso_3166_1": "GB", "rating": "15"}]
+        });
+        assert_eq!(extract_certification(&body, MediaType::Show, "US"), None);
+    }
+
+    #[test]
+    fn extract_certification_returns_none_for_an_empty_certification() {
+        let body = serde_json::json!({
+            "results": [{"iso_3166_1": "US", "rating": ""}]
+        });
+        assert_eq!(extract_certification(&body, MediaType::Show, "US"), None);
+    }
+
+    #[test]
+    fn get_my_rating_returns_none_when_unauthenticated() {
+        let mut trakt = test_trakt();
+        assert_eq!(trakt.get_my_rating(603), None);
+    }
+
+    #[test]
+    fn get_my_rating_reads_from_an_already_populated_cache() {
+        let mut trakt = test_trakt();
+        trakt.oauth_access_token = Some("token".to_string());
+        trakt.my_ratings_cache = Some(HashMap::from([(603, 9)]));
+
+        assert_eq!(trakt.get_my_rating(603), Some(9));
+        assert_eq!(trakt.get_my_rating(1), None);
+    }
+
+    #[test]
+    fn cache_stats_reflects_the_caches_current_sizes() {
+        let mut trakt = test_trakt();
+        assert_eq!(trakt.cache_stats().ratings, 0);
+
+        trakt
+            .rating_cache
+            .insert("the-matrix-1999".to_string(), 8.7);
+        trakt.image_cache.insert(
+            "603".to_string(),
+            "https://example.com/poster.jpg".to_string(),
+        );
+        trakt.company_logo_cache.insert(
+            "603".to_string(),
+            "https://example.com/logo.png".to_string(),
+        );
+
+        let stats = trakt.cache_stats();
+        assert_eq!(stats.ratings, 1);
+        assert_eq!(stats.images, 1);
+        assert_eq!(stats.company_logos, 1);
+    }
+
+    #[test]
+    fn set_oauth_access_token_replaces_the_token_used_for_requests() {
+        let mut trakt = test_trakt();
+        assert_eq!(trakt.oauth_access_token, None);
+
+        trakt.set_oauth_access_token(Some("refreshed".to_string()));
+
+        assert_eq!(trakt.oauth_access_token, Some("refreshed".to_string()));
     }
 }
@@ -1,8 +1,17 @@
 use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
 use configparser::ini::Ini;
+use keyring::Entry;
 use serde::Deserialize;
-use std::{env, io, path::PathBuf, time::Duration};
-use ureq::AgentBuilder;
+use std::{
+    env, fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+use ureq::{Agent, AgentBuilder};
 
 #[derive(Deserialize)]
 pub struct TraktAccessToken {
@@ -16,6 +25,87 @@ pub struct TraktAccessToken {
 
 use crate::trakt::TraktWatchingResponse;
 
+pub const DEFAULT_DISCORD_CLIENT_ID: &str = "826189107046121572";
+
+/// Which of the two presence lines (title vs. episode/rating detail) Discord
+/// should show on top, controlled by the `presenceLineOrder` config key.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PresenceLineOrder {
+    TitleFirst,
+    DetailFirst,
+}
+
+impl PresenceLineOrder {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("detail-first") => PresenceLineOrder::DetailFirst,
+            _ => PresenceLineOrder::TitleFirst,
+        }
+    }
+}
+
+/// Which TMDB artwork a show's presence should use, controlled by the
+/// `showArtwork` config key. `Season` (the default) prefers the current
+/// season's poster and falls back to the show's; `Still` prefers the
+/// current episode's still and falls back to `Season`'s behavior; `Show`
+/// always uses the show's own poster.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArtworkPreference {
+    Show,
+    Season,
+    Still,
+}
+
+impl ArtworkPreference {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("show") => ArtworkPreference::Show,
+            Some("still") => ArtworkPreference::Still,
+            _ => ArtworkPreference::Season,
+        }
+    }
+}
+
+/// Which Discord activity type ("Watching X" / "Listening to X") a presence
+/// should use, controlled by the `activityType` config key. Movies and
+/// episodes default to `Watching`; `Listening` exists for future audio
+/// sources (e.g. a podcast-adjacent `WatchingSource`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ActivityKind {
+    Watching,
+    Listening,
+}
+
+impl ActivityKind {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("listening") => ActivityKind::Listening,
+            _ => ActivityKind::Watching,
+        }
+    }
+}
+
+/// Which of Discord's activity timestamps a presence should set, controlled
+/// by the `timestampMode` config key. Setting both start and end (`Progress`,
+/// the default and discrakt's historical behavior) makes Discord render a
+/// progress bar; setting only one shows a plain elapsed/remaining counter.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TimestampMode {
+    Progress,
+    Elapsed,
+    Remaining,
+}
+
+impl TimestampMode {
+    fn from_config(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("elapsed") => TimestampMode::Elapsed,
+            Some("remaining") => TimestampMode::Remaining,
+            _ => TimestampMode::Progress,
+        }
+    }
+}
+
 pub struct Env {
     pub discord_client_id: String,
     pub trakt_username: String,
@@ -26,36 +116,184 @@ pub struct Env {
     pub trakt_refresh_token: Option<String>,
     pub trakt_refresh_token_expires_at: Option<u64>,
     pub tmdb_token: String,
+    pub show_discuss_button: bool,
+    pub show_studio_logo: bool,
+    pub linger_after_end: u64,
+    pub cache_dir: Option<String>,
+    pub show_my_rating: bool,
+    pub min_progress: f32,
+    pub show_letterboxd_button: bool,
+    pub show_imdb_button: bool,
+    pub presence_line_order: PresenceLineOrder,
+    pub tls_ca_file: Option<String>,
+    pub show_rewatch_indicator: bool,
+    pub trakt_extended: String,
+    pub source_file_path: Option<String>,
+    pub source_combined: bool,
+    pub sleep_wake_gap_threshold: u64,
+    pub language: String,
+    pub compact_episode_state: bool,
+    pub hide_episode_title: bool,
+    pub asset_key_slug: bool,
+    pub activity_kind: ActivityKind,
+    pub trakt_base_url: Option<String>,
+    pub tmdb_base_url: Option<String>,
+    pub presence_enabled: bool,
+    pub anonymize_logs: bool,
+    pub additional_users: Vec<String>,
+    pub scrobble_stop_grace: u64,
+    pub artwork_preference: ArtworkPreference,
+    pub max_poster_resolution: Option<u32>,
+    pub show_pause_indicator: bool,
+    pub small_image: String,
+    pub small_text: String,
+    pub auto_refresh_tokens: bool,
+    pub use_keyring: bool,
+    pub min_presence_update_interval: u64,
+    pub show_certification: bool,
+    pub certification_region: String,
+    pub timestamp_mode: TimestampMode,
+    /// Trakt ids or slugs (comma-separated in `privateTitles`) to never show a
+    /// presence for, since the `watching` response has no per-item privacy
+    /// flag to key off of.
+    pub private_titles: Vec<String>,
+    /// Debug config pinning presence to a specific TMDB movie (`tmdb:27205`)
+    /// regardless of what's actually playing on Trakt, for testing presence
+    /// rendering without needing something queued up to watch.
+    pub pin_item: Option<String>,
+    /// Appends the show's release year to the details line for episodes
+    /// (`"Breaking Bad (2008)"`), controlled by the `showYear` config key.
+    pub show_year: bool,
+    /// Shows "Up next..." instead of clearing the presence during the brief
+    /// gap between one episode's 204 and the next episode's 200 while
+    /// bingeing, controlled by the `bingeHint` config key.
+    pub binge_hint: bool,
+    /// Proactively closes and reconnects the Discord IPC connection after
+    /// this many successful `set_activity` calls, working around Discord
+    /// occasionally dropping updates on a long-lived connection. `None`
+    /// (the default) never reconnects for this reason. `reconnectEveryNUpdates`.
+    pub reconnect_every_n_updates: Option<u32>,
+    /// Shows a brief "just finished" presence at startup when nothing is
+    /// currently watching but `/sync/history` shows the user finished
+    /// something within this many minutes. `None` (the default) disables it.
+    /// Requires OAuth. `recentlyFinishedMinutes`.
+    pub recently_finished_minutes: Option<u64>,
+    /// Base domain for the presence's IMDB button links (`imdbBase`), for
+    /// regions where imdb.com is blocked/mirrored. Defaults to the real domain.
+    pub imdb_link_base: String,
+    /// Base domain for the presence's Trakt/Discuss button links (`traktBase`),
+    /// for regions where trakt.tv is blocked/mirrored. Defaults to the real domain.
+    pub trakt_link_base: String,
 }
 
 pub struct WatchStats {
-    pub watch_percentage: String,
+    /// `None` when `percentage` couldn't be computed, e.g. a movie with
+    /// neither `expires_at` nor a known runtime, so there's no watch window
+    /// to measure elapsed time against.
+    pub watch_percentage: Option<String>,
+    pub percentage: Option<f32>,
     pub start_date: DateTime<FixedOffset>,
     pub end_date: DateTime<FixedOffset>,
 }
 
+/// Masks a secret to only its last 4 characters (fully masked if shorter),
+/// so `--print-config` output is safe to paste into a bug report.
+fn mask_secret(secret: &str) -> String {
+    let char_count = secret.chars().count();
+    if char_count <= 4 {
+        return "*".repeat(char_count);
+    }
+    let tail: String = secret
+        .chars()
+        .rev()
+        .take(4)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    format!("{}{tail}", "*".repeat(char_count - 4))
+}
+
 impl Env {
+    /// Whether the user overrode the default Discord application id, which is
+    /// useful to know when triaging support requests.
+    pub fn has_custom_client_id(&self) -> bool {
+        self.discord_client_id != DEFAULT_DISCORD_CLIENT_ID
+    }
+
+    /// Builds a redacted, human-readable summary of the resolved config for
+    /// `--print-config`: tokens are masked to their last 4 characters, so it's
+    /// safe to paste directly into a bug report.
+    pub fn redacted_summary(&self) -> String {
+        [
+            format!("trakt_username: {}", self.trakt_username),
+            format!(
+                "discord_client_id: {} ({})",
+                mask_secret(&self.discord_client_id),
+                if self.has_custom_client_id() {
+                    "custom"
+                } else {
+                    "default"
+                }
+            ),
+            format!("trakt_client_id: {}", mask_secret(&self.trakt_client_id)),
+            format!("trakt_oauth_enabled: {}", self.trakt_oauth_enabled),
+            format!(
+                "trakt_access_token: {}",
+                match self.trakt_access_token.as_deref() {
+                    Some(token) if !token.is_empty() => mask_secret(token),
+                    _ => "none".to_string(),
+                }
+            ),
+            format!("tmdb_token: {}", mask_secret(&self.tmdb_token)),
+            format!("language: {}", self.language),
+            format!("presence_enabled: {}", self.presence_enabled),
+            format!("anonymize_logs: {}", self.anonymize_logs),
+            format!("show_year: {}", self.show_year),
+            format!("binge_hint: {}", self.binge_hint),
+            format!("private_titles: {} configured", self.private_titles.len()),
+            format!(
+                "additional_users: {} configured",
+                self.additional_users.len()
+            ),
+        ]
+        .join("\n")
+    }
+
     pub fn check_oauth(&mut self) {
         if self.trakt_oauth_enabled {
             if self.trakt_access_token.is_none()
                 || self.trakt_access_token.as_ref().unwrap().is_empty()
             {
                 self.authorize_app();
-            } else if let Some(expires_at) = self.trakt_refresh_token_expires_at {
-                if Utc::now().timestamp() as u64 > expires_at {
-                    self.exchange_refresh_token_for_access_token();
+            } else if self.auto_refresh_tokens {
+                if let Some(expires_at) = self.trakt_refresh_token_expires_at {
+                    if Utc::now().timestamp() as u64 > expires_at {
+                        self.exchange_refresh_token_for_access_token();
+                    }
                 }
             }
         }
     }
 
+    // discrakt's OAuth flow is the manual "out of band" one: the user opens
+    // `auth_url`, approves, and pastes the resulting code into the terminal
+    // for `exchange_code_for_access_token` to read - there's no local setup
+    // server in this build (see `lib.rs`) for a browser tab to time out
+    // waiting on, so there's no `SUCCESS_GRACE_PERIOD`-style constant here to
+    // make configurable or derive from a device-code `interval`, and
+    // consequently no grace-period computation to test. Same reason there's
+    // no `/submit` handler or `SubmittedCredentials` type to add a
+    // urlencoded-form parser to, and so nothing there to add content-type
+    // tests for either.
     fn authorize_app(&mut self) {
-        if webbrowser::open(
-            &format!("https://trakt.tv/oauth/authorize?response_type=code&client_id={}&redirect_uri=urn:ietf:wg:oauth:2.0:oob", self.trakt_client_id)
-        ).is_err() {
-            eprintln!("Failed to open webbrowser to authorize discrakt");
-            return;
-        };
+        let auth_url = format!("https://trakt.tv/oauth/authorize?response_type=code&client_id={}&redirect_uri=urn:ietf:wg:oauth:2.0:oob", self.trakt_client_id);
+
+        if is_headless() {
+            println!("No display detected, open this URL to authorize discrakt: {auth_url}");
+        } else if webbrowser::open(&auth_url).is_err() {
+            println!("Failed to open webbrowser, open this URL to authorize discrakt: {auth_url}");
+        }
         self.exchange_code_for_access_token();
     }
 
@@ -69,10 +307,7 @@ impl Env {
             .expect("Failed to read line");
         let code = code.trim();
 
-        let agent = AgentBuilder::new()
-            .timeout_read(Duration::from_secs(5))
-            .timeout_write(Duration::from_secs(5))
-            .build();
+        let agent = build_agent(self.tls_ca_file.as_deref());
         let response = match agent
             .post("https://api.trakt.tv/oauth/token")
             .set("Content-Type", "application/json")
@@ -95,17 +330,14 @@ impl Env {
             self.trakt_refresh_token = Some(json_response.refresh_token.clone());
             self.trakt_refresh_token_expires_at =
                 Some(json_response.created_at + 60 * 60 * 24 * 30 * 3); // secs * mins * hours * days * months => 3 months
-            set_oauth_tokens(&json_response);
+            set_oauth_tokens(&self.trakt_username, self.use_keyring, &json_response);
         } else {
             eprintln!("Failed to exchange code for access token");
         }
     }
 
     fn exchange_refresh_token_for_access_token(&mut self) {
-        let agent = AgentBuilder::new()
-            .timeout_read(Duration::from_secs(5))
-            .timeout_write(Duration::from_secs(5))
-            .build();
+        let agent = build_agent(self.tls_ca_file.as_deref());
         let response = match agent
             .post("https://api.trakt.tv/oauth/token")
             .set("Content-Type", "application/json")
@@ -128,15 +360,137 @@ impl Env {
             self.trakt_refresh_token = Some(json_response.refresh_token.clone());
             self.trakt_refresh_token_expires_at =
                 Some(json_response.created_at + 60 * 60 * 24 * 30 * 3); // secs * mins * hours * days * months => 3 months
-            set_oauth_tokens(&json_response);
+            set_oauth_tokens(&self.trakt_username, self.use_keyring, &json_response);
         } else {
             eprintln!("Failed to exchange refresh token for access token");
         }
     }
 }
 
+/// Builds a rustls root store from a PEM-encoded CA bundle, for environments
+/// (corporate proxies, custom CAs) where the bundled webpki roots aren't enough.
+fn load_custom_root_store(ca_file: &str) -> io::Result<rustls::RootCertStore> {
+    let pem = fs::read(ca_file)?;
+    let certs: Vec<_> = rustls_pemfile::certs(&mut pem.as_slice()).collect::<Result<_, _>>()?;
+    let mut root_store = rustls::RootCertStore::empty();
+    let (added, _ignored) = root_store.add_parsable_certificates(certs);
+    if added == 0 {
+        return Err(io::Error::other("no valid certificates found in CA file"));
+    }
+    Ok(root_store)
+}
+
+/// Builds the HTTP agent used for all Trakt/TMDB calls, honoring an optional
+/// `tlsCaFile`/`SSL_CERT_FILE` override for restricted networks with custom CAs.
+/// Falls back to the default (webpki) root store if the file can't be loaded.
+pub fn build_agent(ca_file: Option<&str>) -> Agent {
+    let builder = AgentBuilder::new()
+        .timeout_read(Duration::from_secs(5))
+        .timeout_write(Duration::from_secs(5));
+
+    let ca_file = ca_file
+        .map(str::to_string)
+        .or_else(|| env::var("SSL_CERT_FILE").ok());
+
+    match ca_file {
+        Some(ca_file) => match load_custom_root_store(&ca_file) {
+            Ok(root_store) => {
+                let tls_config = rustls::ClientConfig::builder()
+                    .with_root_certificates(root_store)
+                    .with_no_client_auth();
+                builder.tls_config(Arc::new(tls_config)).build()
+            }
+            Err(e) => {
+                eprintln!("Failed to load custom CA file {ca_file:?}: {e}, using default roots");
+                builder.build()
+            }
+        },
+        None => builder.build(),
+    }
+}
+
+/// Resolves the directory disk-backed caches should live in: an explicit
+/// `[Discrakt] cacheDir` override if it's a valid directory, otherwise
+/// `dirs::cache_dir()/discrakt`.
+pub fn cache_dir_path(override_dir: Option<&str>) -> PathBuf {
+    if let Some(dir) = override_dir {
+        let path = PathBuf::from(dir);
+        if path.is_dir() {
+            return path;
+        }
+        eprintln!("Configured cacheDir {dir:?} is not a valid directory, falling back to default");
+    }
+
+    let default = dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("discrakt");
+    let _ = std::fs::create_dir_all(&default);
+    default
+}
+
+/// Resolves the directory discrakt's daily log files live in:
+/// `dirs::cache_dir()/discrakt/logs`.
+pub fn log_dir_path() -> PathBuf {
+    let dir = cache_dir_path(None).join("logs");
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+/// Path to today's log file, named `discrakt.YYYY-MM-DD.log`.
+pub fn current_log_file_path() -> PathBuf {
+    log_dir_path().join(format!("discrakt.{}.log", Utc::now().format("%Y-%m-%d")))
+}
+
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Overrides the config file location used by `find_config_file`, e.g. from a
+/// `--config <path>` CLI argument. Can only be set once per process.
+pub fn set_config_path_override(path: PathBuf) {
+    let _ = CONFIG_PATH_OVERRIDE.set(path);
+}
+
+/// Resolves the directory `credentials.ini` is expected to live in, falling
+/// back through `dirs::config_dir()` -> `$HOME/.config` -> the current
+/// executable's directory instead of panicking when a platform can't
+/// determine a config directory. This is the only place in the codebase that
+/// resolves this path - there's no separate `setup/server.rs` writer in this
+/// build (no local HTTP setup server at all, see `authorize_app`'s note) that
+/// could disagree with `find_config_file` below on where to look, and so no
+/// second path to write an agreement test against; `config_dir_path_given`'s
+/// own tests below already cover this function's fallback logic directly.
+fn config_dir_path() -> PathBuf {
+    config_dir_path_given(dirs::config_dir(), dirs::home_dir(), || {
+        let mut exe_path = env::current_exe().unwrap();
+        exe_path.pop();
+        exe_path
+    })
+}
+
+/// The fallback-selection half of `config_dir_path`, taking each candidate as
+/// a parameter instead of calling `dirs`/`env::current_exe` itself so the
+/// fallback order can be tested without depending on the real platform's
+/// config/home directories.
+fn config_dir_path_given(
+    config_dir: Option<PathBuf>,
+    home_dir: Option<PathBuf>,
+    exe_dir: impl FnOnce() -> PathBuf,
+) -> PathBuf {
+    if let Some(dir) = config_dir {
+        return dir.join("discrakt");
+    }
+    if let Some(home) = home_dir {
+        return home.join(".config").join("discrakt");
+    }
+
+    exe_dir()
+}
+
 fn find_config_file() -> Option<PathBuf> {
-    let config_path = dirs::config_dir().unwrap().join("discrakt");
+    if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+        return Some(path.clone());
+    }
+
+    let config_path = config_dir_path();
     let mut exe_path = env::current_exe().unwrap();
     exe_path.pop();
 
@@ -165,11 +519,51 @@ pub fn load_config() -> Env {
     let path = config_file.expect("Could not find credentials.ini");
     config.load(path).expect("Failed to load credentials.ini");
 
+    parse_env(&config)
+}
+
+/// Maps a loaded `credentials.ini` onto `Env`, applying every key's default.
+/// Split out from `load_config` so the mapping itself - which key defaults to
+/// what - can be exercised against an in-memory `Ini` instead of a real file.
+fn parse_env(config: &Ini) -> Env {
+    let trakt_username = config
+        .get("Trakt API", "traktUser")
+        .expect("traktUser not found");
+    let use_keyring = config
+        .getbool("Discrakt", "useKeyring")
+        .unwrap_or(None)
+        .unwrap_or(false);
+
+    // Non-secret fields (everything but the tokens themselves) always live in
+    // credentials.ini; only the tokens move to the keyring, with a fallback
+    // to whatever's in the ini if the keyring is unavailable or empty.
+    let (trakt_access_token, trakt_refresh_token) = if use_keyring {
+        (
+            load_secret_from_keyring(&trakt_username, "access-token")
+                .or_else(|| config.get("Trakt API", "OAuthAccessToken")),
+            load_secret_from_keyring(&trakt_username, "refresh-token")
+                .or_else(|| config.get("Trakt API", "OAuthRefreshToken")),
+        )
+    } else {
+        (
+            config.get("Trakt API", "OAuthAccessToken"),
+            config.get("Trakt API", "OAuthRefreshToken"),
+        )
+    };
+
     Env {
-        discord_client_id: "826189107046121572".to_string(),
-        trakt_username: config
-            .get("Trakt API", "traktUser")
-            .expect("traktUser not found"),
+        // `[Discord] discordClientID` was the pre-`[Discrakt]` section name for
+        // this key; still read as a fallback so an old credentials.ini keeps
+        // working after an upgrade. There's no `appIdMovie`/`appIdShow` split:
+        // `Discord::new` opens one IPC connection bound to a single client id
+        // for the process's lifetime, and swapping it on every movie/show
+        // switch would mean reconnecting (and losing presence continuity)
+        // far more often than the current per-run connection.
+        discord_client_id: config
+            .get("Discrakt", "discordClientId")
+            .or_else(|| config.get("Discord", "discordClientID"))
+            .unwrap_or_else(|| DEFAULT_DISCORD_CLIENT_ID.to_string()),
+        trakt_username,
         trakt_client_id: config
             .get("Trakt API", "traktClientID")
             .expect("traktClientID not found"),
@@ -178,63 +572,572 @@ pub fn load_config() -> Env {
             .expect("enableOAuth not found")
             .unwrap_or(false),
         trakt_client_secret: config.get("Trakt API", "traktClientSecret"),
-        trakt_access_token: config.get("Trakt API", "OAuthAccessToken"),
-        trakt_refresh_token: config.get("Trakt API", "OAuthRefreshToken"),
+        trakt_access_token,
+        trakt_refresh_token,
         trakt_refresh_token_expires_at: config
             .getuint("Trakt API", "OAuthRefreshTokenExpiresAt")
             .unwrap_or_default(),
         tmdb_token: "21b815a75fec5f1e707e3da1b9b2d7e3".to_string(),
+        show_discuss_button: config
+            .getbool("Discrakt", "showDiscussButton")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        show_studio_logo: config
+            .getbool("Discrakt", "showStudioLogo")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        linger_after_end: config
+            .getuint("Discrakt", "lingerAfterEnd")
+            .unwrap_or(None)
+            .unwrap_or(0),
+        cache_dir: config.get("Discrakt", "cacheDir"),
+        show_my_rating: config
+            .getbool("Discrakt", "showMyRating")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        min_progress: config
+            .getfloat("Discrakt", "minProgress")
+            .unwrap_or(None)
+            .unwrap_or(0.0) as f32,
+        show_letterboxd_button: config
+            .getbool("Discrakt", "showLetterboxdButton")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        show_imdb_button: config
+            .getbool("Discrakt", "showImdbButton")
+            .unwrap_or(None)
+            .unwrap_or(true),
+        presence_line_order: PresenceLineOrder::from_config(
+            config.get("Discrakt", "presenceLineOrder"),
+        ),
+        tls_ca_file: config.get("Discrakt", "tlsCaFile"),
+        show_rewatch_indicator: config
+            .getbool("Discrakt", "showRewatchIndicator")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        trakt_extended: config
+            .get("Discrakt", "traktExtended")
+            .unwrap_or_else(|| "full".to_string()),
+        source_file_path: config
+            .get("Discrakt", "source")
+            .filter(|source| source == "file" || source == "combined")
+            .and_then(|_| config.get("Discrakt", "sourceFilePath")),
+        source_combined: config.get("Discrakt", "source").as_deref() == Some("combined"),
+        sleep_wake_gap_threshold: config
+            .getuint("Discrakt", "sleepWakeGapThreshold")
+            .unwrap_or(None)
+            .unwrap_or(60),
+        language: config
+            .get("Discrakt", "language")
+            .unwrap_or_else(|| "en-US".to_string()),
+        compact_episode_state: config
+            .getbool("Discrakt", "compactEpisodeState")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        hide_episode_title: config
+            .getbool("Discrakt", "hideEpisodeTitle")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        asset_key_slug: config.get("Discrakt", "assetKey").as_deref() == Some("slug"),
+        activity_kind: ActivityKind::from_config(config.get("Discrakt", "activityType")),
+        trakt_base_url: config.get("Discrakt", "traktBaseUrl"),
+        tmdb_base_url: config.get("Discrakt", "tmdbBaseUrl"),
+        presence_enabled: config
+            .getbool("Discrakt", "presenceEnabled")
+            .unwrap_or(None)
+            .unwrap_or(true),
+        anonymize_logs: config
+            .getbool("Discrakt", "anonymizeLogs")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        additional_users: config
+            .get("Trakt API", "additionalUsers")
+            .map(|users| {
+                users
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|user| !user.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        scrobble_stop_grace: config
+            .getuint("Discrakt", "scrobbleStopGrace")
+            .unwrap_or(None)
+            .unwrap_or(20),
+        artwork_preference: ArtworkPreference::from_config(config.get("Discrakt", "showArtwork")),
+        max_poster_resolution: config
+            .getuint("Discrakt", "maxPosterResolution")
+            .unwrap_or(None)
+            .map(|value| value as u32),
+        show_pause_indicator: config
+            .getbool("Discrakt", "showPauseIndicator")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        small_image: config
+            .get("Discrakt", "smallImage")
+            .unwrap_or_else(|| "trakt".to_string()),
+        small_text: config
+            .get("Discrakt", "smallText")
+            .unwrap_or_else(|| "Discrakt".to_string()),
+        auto_refresh_tokens: config
+            .getbool("Discrakt", "autoRefreshTokens")
+            .unwrap_or(None)
+            .unwrap_or(true),
+        use_keyring,
+        min_presence_update_interval: config
+            .getuint("Discrakt", "minPresenceUpdateInterval")
+            .unwrap_or(None)
+            .unwrap_or(15),
+        show_certification: config
+            .getbool("Discrakt", "showCertification")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        certification_region: config
+            .get("Discrakt", "certificationRegion")
+            .unwrap_or_else(|| "US".to_string()),
+        timestamp_mode: TimestampMode::from_config(config.get("Discrakt", "timestampMode")),
+        private_titles: config
+            .get("Discrakt", "privateTitles")
+            .map(|titles| {
+                titles
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|title| !title.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        pin_item: config.get("Discrakt", "pinItem"),
+        show_year: config
+            .getbool("Discrakt", "showYear")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        binge_hint: config
+            .getbool("Discrakt", "bingeHint")
+            .unwrap_or(None)
+            .unwrap_or(false),
+        reconnect_every_n_updates: config
+            .getuint("Discrakt", "reconnectEveryNUpdates")
+            .unwrap_or(None)
+            .map(|n| n as u32),
+        recently_finished_minutes: config
+            .getuint("Discrakt", "recentlyFinishedMinutes")
+            .unwrap_or(None),
+        imdb_link_base: config
+            .get("Discrakt", "imdbBase")
+            .map(|base| base.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| "https://www.imdb.com".to_string()),
+        trakt_link_base: config
+            .get("Discrakt", "traktBase")
+            .map(|base| base.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| "https://trakt.tv".to_string()),
     }
 }
 
-fn set_oauth_tokens(json_response: &TraktAccessToken) {
-    let mut config = Ini::new_cs();
-    let config_file = find_config_file();
+/// Abstracts over where the `credentials.ini` data lives, so the token-persistence
+/// logic can be exercised against something other than a real file on disk.
+pub trait ConfigStore {
+    fn load(&self) -> Ini;
+    fn save(&self, config: Ini);
+}
 
-    let path = config_file.expect("Could not find credentials.ini");
+/// The real, file-backed `credentials.ini` store used at runtime.
+pub struct FileConfigStore {
+    path: PathBuf,
+}
+
+impl FileConfigStore {
+    pub fn new(path: PathBuf) -> Self {
+        FileConfigStore { path }
+    }
+}
+
+impl ConfigStore for FileConfigStore {
+    fn load(&self) -> Ini {
+        let mut config = Ini::new_cs();
+        config
+            .load(&self.path)
+            .expect("Failed to load credentials.ini");
+        config
+    }
+
+    fn save(&self, config: Ini) {
+        config
+            .write(&self.path)
+            .expect("Failed to write credentials.ini");
+    }
+}
+
+/// An in-memory `ConfigStore` for unit tests, avoiding any real filesystem access.
+pub struct InMemoryConfigStore {
+    config: std::cell::RefCell<Ini>,
+}
+
+impl InMemoryConfigStore {
+    pub fn new(config: Ini) -> Self {
+        InMemoryConfigStore {
+            config: std::cell::RefCell::new(config),
+        }
+    }
+}
+
+impl ConfigStore for InMemoryConfigStore {
+    fn load(&self) -> Ini {
+        self.config.borrow().clone()
+    }
+
+    fn save(&self, config: Ini) {
+        *self.config.borrow_mut() = config;
+    }
+}
+
+const KEYRING_SERVICE: &str = "discrakt";
+
+/// Loads a secret (an OAuth token) from the OS keyring. Returns `None` both
+/// when there's genuinely no entry yet and when the keyring backend itself is
+/// unavailable (e.g. headless Linux without a secret service running) - in the
+/// latter case a warning is printed so the fallback to `credentials.ini`
+/// doesn't look like a silently missing token.
+fn load_secret_from_keyring(username: &str, field: &str) -> Option<String> {
+    let entry = match Entry::new(KEYRING_SERVICE, &format!("{username}-{field}")) {
+        Ok(entry) => entry,
+        Err(e) => {
+            eprintln!("Keyring unavailable ({e}), falling back to credentials.ini for {field}");
+            return None;
+        }
+    };
+    match entry.get_password() {
+        Ok(secret) => Some(secret),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            eprintln!("Keyring unavailable ({e}), falling back to credentials.ini for {field}");
+            None
+        }
+    }
+}
+
+/// Saves a secret to the OS keyring. Returns whether it succeeded, so the
+/// caller can decide whether the plaintext copy in `credentials.ini` is still
+/// needed as a fallback.
+fn save_secret_to_keyring(username: &str, field: &str, secret: &str) -> bool {
+    let entry = match Entry::new(KEYRING_SERVICE, &format!("{username}-{field}")) {
+        Ok(entry) => entry,
+        Err(e) => {
+            eprintln!("Keyring unavailable ({e}), falling back to credentials.ini for {field}");
+            return false;
+        }
+    };
+    match entry.set_password(secret) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Keyring unavailable ({e}), falling back to credentials.ini for {field}");
+            false
+        }
+    }
+}
+
+fn set_oauth_tokens_with_store(
+    store: &dyn ConfigStore,
+    username: &str,
+    use_keyring: bool,
+    json_response: &TraktAccessToken,
+) {
+    let mut config = store.load();
+
+    let access_token_in_keyring = use_keyring
+        && save_secret_to_keyring(username, "access-token", &json_response.access_token);
+    let refresh_token_in_keyring = use_keyring
+        && save_secret_to_keyring(username, "refresh-token", &json_response.refresh_token);
 
-    config
-        .load(path.clone())
-        .expect("Failed to load credentials.ini");
     config.setstr(
         "Trakt API",
         "OAuthAccessToken",
-        Some(json_response.access_token.as_str()),
+        (!access_token_in_keyring).then_some(json_response.access_token.as_str()),
     );
     config.setstr(
         "Trakt API",
         "OAuthRefreshToken",
-        Some(json_response.refresh_token.as_str()),
+        (!refresh_token_in_keyring).then_some(json_response.refresh_token.as_str()),
     );
     config.set(
         "Trakt API",
         "OAuthRefreshTokenExpiresAt",
         Some(json_response.created_at.to_string()),
     );
-    config.write(path).expect("Failed to write credentials.ini");
+    store.save(config);
+}
+
+fn set_oauth_tokens(username: &str, use_keyring: bool, json_response: &TraktAccessToken) {
+    let path = find_config_file().expect("Could not find credentials.ini");
+    set_oauth_tokens_with_store(
+        &FileConfigStore::new(path),
+        username,
+        use_keyring,
+        json_response,
+    );
+}
+
+fn set_presence_enabled_with_store(store: &dyn ConfigStore, enabled: bool) {
+    let mut config = store.load();
+    config.setstr("Discrakt", "presenceEnabled", Some(&enabled.to_string()));
+    store.save(config);
+}
+
+/// Persists the `presenceEnabled` toggle to `credentials.ini`, so pushing to
+/// Discord can be turned off without also disabling Trakt polling/history.
+pub fn set_presence_enabled(enabled: bool) {
+    let path = find_config_file().expect("Could not find credentials.ini");
+    set_presence_enabled_with_store(&FileConfigStore::new(path), enabled);
+}
+
+fn save_language_with_store(store: &dyn ConfigStore, code: &str) {
+    let mut config = store.load();
+    config.setstr("Discrakt", "language", Some(code));
+    store.save(config);
+}
+
+/// Persists a runtime-selected language to `credentials.ini`, so it survives
+/// a restart instead of resetting to the configured default.
+pub fn save_language(code: &str) {
+    let path = find_config_file().expect("Could not find credentials.ini");
+    save_language_with_store(&FileConfigStore::new(path), code);
+}
+
+static LOG_ANONYMIZATION: OnceLock<Option<String>> = OnceLock::new();
+
+/// Enables replacing the configured Trakt username with a placeholder in log
+/// output, for users sharing logs publicly. Can only be set once per process.
+pub fn set_log_anonymization(username: Option<String>) {
+    let _ = LOG_ANONYMIZATION.set(username);
+}
+
+/// Replaces every occurrence of `username` in `message` with a placeholder,
+/// when log anonymization is enabled.
+fn sanitize_log(message: &str, username: &str) -> String {
+    message.replace(username, "<user>")
+}
+
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+/// Suppresses `log`'s console output for scripted/`--quiet` runs, while
+/// leaving the log file and one-off `println!` confirmations
+/// (`--enable-autostart`, `--logs`, etc.) untouched. There's no severity-tiered logger
+/// here to filter by level, so this is an all-or-nothing switch on routine
+/// `log()` calls. Can only be set once per process.
+pub fn set_quiet(quiet: bool) {
+    let _ = QUIET.set(quiet);
+}
+
+fn is_quiet() -> bool {
+    QUIET.get().copied().unwrap_or(false)
 }
 
 pub fn log(message: &str) {
-    println!(
+    let message = match LOG_ANONYMIZATION.get() {
+        Some(Some(username)) => sanitize_log(message, username),
+        _ => message.to_string(),
+    };
+    let line = format!(
         "{} : {message}",
         Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
     );
+    if !is_quiet() {
+        println!("{line}");
+    }
+
+    match fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(current_log_file_path())
+    {
+        Ok(mut file) => {
+            use io::Write;
+            let _ = writeln!(file, "{line}");
+        }
+        Err(e) => warn_log_file_unavailable(&e),
+    }
+}
+
+static LOG_FILE_UNAVAILABLE_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Prints a one-time warning to stderr when the log file can't be opened
+/// (e.g. a read-only log directory), so scrobbling keeps working with
+/// console-only logging instead of silently dropping every line to the file.
+/// Only warns once per process to avoid spamming stderr on every poll tick.
+fn warn_log_file_unavailable(e: &io::Error) {
+    if !LOG_FILE_UNAVAILABLE_WARNED.swap(true, Ordering::SeqCst) {
+        eprintln!(
+            "{}",
+            log_file_unavailable_message(&current_log_file_path(), e)
+        );
+    }
+}
+
+/// Builds the warning text for `warn_log_file_unavailable`, pulled out so the
+/// wording can be tested without needing a real unwritable path or capturing
+/// stderr.
+fn log_file_unavailable_message(path: &std::path::Path, e: &io::Error) -> String {
+    format!(
+        "Could not open log file at {} ({e}), continuing with console-only logging",
+        path.display()
+    )
 }
 
 pub fn get_watch_stats(trakt_response: &TraktWatchingResponse) -> WatchStats {
-    let start_date = DateTime::parse_from_rfc3339(&trakt_response.started_at).unwrap();
-    let end_date = DateTime::parse_from_rfc3339(&trakt_response.expires_at).unwrap();
-    let percentage = Utc::now().signed_duration_since(start_date).num_seconds() as f32
-        / end_date.signed_duration_since(start_date).num_seconds() as f32;
-    let watch_percentage = format!("{:.2}%", percentage * 100.0);
+    let raw_start_date = DateTime::parse_from_rfc3339(&trakt_response.started_at).unwrap();
+    // A pre-scheduled Trakt checkin can have `started_at` in the future; using
+    // it as-is would give a negative elapsed time and a negative percentage.
+    // Clamping to "now" instead treats it the same as a checkin that just
+    // started, which is the closest sane presence to show.
+    let now = Utc::now();
+    let start_date = if raw_start_date.timestamp() > now.timestamp() {
+        now.into()
+    } else {
+        raw_start_date
+    };
+    let end_date = match trakt_response
+        .expires_at
+        .as_deref()
+        .filter(|expires_at| !expires_at.is_empty())
+    {
+        Some(expires_at) => DateTime::parse_from_rfc3339(expires_at).unwrap(),
+        None => {
+            let runtime_minutes = trakt_response
+                .movie
+                .as_ref()
+                .and_then(|movie| movie.runtime)
+                .unwrap_or(0);
+            start_date + chrono::Duration::minutes(runtime_minutes as i64)
+        }
+    };
+    let window_seconds = end_date.signed_duration_since(start_date).num_seconds();
+    let percentage = trakt_response.progress.or_else(|| {
+        (window_seconds > 0).then(|| {
+            100.0 * now.signed_duration_since(start_date).num_seconds() as f32
+                / window_seconds as f32
+        })
+    });
+    let watch_percentage = percentage.map(|percentage| format!("{percentage:.2}%"));
 
     WatchStats {
         watch_percentage,
+        percentage,
         start_date,
         end_date,
     }
 }
 
+/// Whether the currently watched movie/show matches an entry in
+/// `privateTitles` (by numeric Trakt id or slug), so the polling loop can
+/// skip presence for titles the user opted out of sharing. There's no
+/// per-item privacy flag in the `watching` response itself to key off of.
+pub fn is_private_title(trakt_response: &TraktWatchingResponse, private_titles: &[String]) -> bool {
+    if private_titles.is_empty() {
+        return false;
+    }
+    let ids = trakt_response
+        .movie
+        .as_ref()
+        .map(|movie| &movie.ids)
+        .or_else(|| trakt_response.show.as_ref().map(|show| &show.ids));
+    let Some(ids) = ids else {
+        return false;
+    };
+    private_titles
+        .iter()
+        .any(|title| *title == ids.trakt.to_string() || ids.slug.as_deref() == Some(title))
+}
+
+/// Whether playback appears paused: the same title (identified by its
+/// `started_at`) reporting the same watch percentage across consecutive
+/// polls, rather than genuinely advancing.
+pub fn is_playback_stalled(
+    last_progress: &Option<(String, Option<f32>)>,
+    started_at: &str,
+    percentage: Option<f32>,
+) -> bool {
+    matches!(
+        last_progress,
+        Some((last_started_at, last_percentage))
+            if last_started_at == started_at && *last_percentage == percentage
+    )
+}
+
+/// Whether the gap since the previous poll is large enough to indicate the
+/// machine was asleep, meaning the Discord IPC connection is likely dead and
+/// should be proactively reconnected instead of waiting for `set_activity` to fail.
+pub fn is_sleep_wake_gap(elapsed_since_last_poll: Duration, threshold: Duration) -> bool {
+    elapsed_since_last_poll > threshold
+}
+
+/// Picks the next username to poll for "family mode" (the configured user plus
+/// `additionalUsers`), cycling through `users` in order. Returns `None` when
+/// there's only one user configured, so callers can tell "rotate" from "don't
+/// prefix the presence with a username at all".
+pub fn next_rotation_user(users: &[String], index: usize) -> Option<String> {
+    if users.len() <= 1 {
+        return None;
+    }
+    Some(users[index % users.len()].clone())
+}
+
+/// Whether the current watch percentage is below `minProgress` and should be
+/// gated from presence. Checkins report no watch window to measure progress
+/// against, so an unknown percentage can't be below the threshold - showing
+/// the presence is a more useful default than silently skipping it.
+pub fn is_below_min_progress(percentage: Option<f32>, min_progress: f32) -> bool {
+    percentage.is_some_and(|percentage| percentage < min_progress)
+}
+
+/// Formats a single-decimal rating using the decimal separator conventional
+/// for the given language (e.g. "8,5" for `fr-FR` vs "8.5" for `en-US`),
+/// keyed off `Env.language`. Defaults to `.` for anything not recognized.
+pub fn format_rating(rating: f64, language: &str) -> String {
+    let formatted = format!("{rating:.1}");
+    if language.starts_with("fr") {
+        formatted.replace('.', ",")
+    } else {
+        formatted
+    }
+}
+
+/// Best-effort light/dark theme detection on Linux without a Tokio runtime,
+/// by reading the GTK/KDE color-scheme hints directly instead of going through
+/// `dark-light`'s `zbus` portal call. Discrakt has no tray icon in this build,
+/// but this keeps the detection usable once one is added.
+#[cfg(target_os = "linux")]
+pub fn is_light_mode_linux() -> Option<bool> {
+    if let Ok(scheme) = env::var("GTK_THEME") {
+        return Some(!scheme.to_lowercase().contains("dark"));
+    }
+    if let Ok(scheme) = env::var("COLOR_SCHEME") {
+        return Some(scheme.eq_ignore_ascii_case("light"));
+    }
+    None
+}
+
+/// Whether there's no display to open a browser on, so OAuth setup should fall
+/// back to printing the authorization URL instead of launching a browser.
+#[cfg(target_os = "linux")]
+pub fn is_headless() -> bool {
+    is_headless_given(env::var("DISPLAY").ok(), env::var("WAYLAND_DISPLAY").ok())
+}
+
+/// The decision half of `is_headless`, taking the two display env vars as
+/// parameters instead of reading them itself so it can be tested without
+/// mutating process-wide environment state.
+#[cfg(target_os = "linux")]
+fn is_headless_given(display: Option<String>, wayland_display: Option<String>) -> bool {
+    display.is_none() && wayland_display.is_none()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_headless() -> bool {
+    false
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum MediaType {
     Show,
     Movie,
@@ -248,3 +1151,783 @@ impl MediaType {
         }
     }
 }
+
+impl std::fmt::Display for MediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trakt::{TraktIds, TraktMovie};
+
+    fn movie_watching(
+        started_at: &str,
+        expires_at: Option<&str>,
+        runtime: Option<u32>,
+    ) -> TraktWatchingResponse {
+        TraktWatchingResponse {
+            expires_at: expires_at.map(|s| s.to_string()),
+            started_at: started_at.to_string(),
+            action: "watching".to_string(),
+            r#type: "movie".to_string(),
+            movie: Some(TraktMovie {
+                title: "The Matrix".to_string(),
+                year: 1999,
+                ids: TraktIds {
+                    trakt: 1,
+                    slug: Some("the-matrix-1999".to_string()),
+                    tvdb: None,
+                    imdb: None,
+                    tmdb: Some(603),
+                    tvrage: None,
+                },
+                runtime,
+            }),
+            show: None,
+            episode: None,
+            is_rewatch: None,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn mask_secret_keeps_only_the_last_four_characters_visible() {
+        assert_eq!(mask_secret("abcdef1234"), "******1234");
+    }
+
+    #[test]
+    fn mask_secret_fully_masks_secrets_at_or_under_four_characters() {
+        assert_eq!(mask_secret("abcd"), "****");
+        assert_eq!(mask_secret("ab"), "**");
+    }
+
+    #[test]
+    fn mask_secret_fully_masks_an_empty_secret() {
+        assert_eq!(mask_secret(""), "");
+    }
+
+    #[test]
+    fn mask_secret_does_not_panic_on_multi_byte_characters() {
+        assert_eq!(mask_secret("ab€gh"), "*b€gh");
+    }
+
+    #[test]
+    fn set_oauth_tokens_persists_tokens_and_expiry_without_keyring() {
+        let store = InMemoryConfigStore::new(Ini::new_cs());
+        let json_response = TraktAccessToken {
+            access_token: "access-123".to_string(),
+            token_type: "bearer".to_string(),
+            expires_in: 7200,
+            refresh_token: "refresh-456".to_string(),
+            scope: "public".to_string(),
+            created_at: 1_700_000_000,
+        };
+
+        set_oauth_tokens_with_store(&store, "someone", false, &json_response);
+
+        let saved = store.load();
+        assert_eq!(
+            saved.get("Trakt API", "OAuthAccessToken").as_deref(),
+            Some("access-123")
+        );
+        assert_eq!(
+            saved.get("Trakt API", "OAuthRefreshToken").as_deref(),
+            Some("refresh-456")
+        );
+        assert_eq!(
+            saved
+                .get("Trakt API", "OAuthRefreshTokenExpiresAt")
+                .as_deref(),
+            Some("1700000000")
+        );
+    }
+
+    #[test]
+    fn set_presence_enabled_persists_flag() {
+        let store = InMemoryConfigStore::new(Ini::new_cs());
+
+        set_presence_enabled_with_store(&store, false);
+
+        let saved = store.load();
+        assert_eq!(
+            saved.get("Discrakt", "presenceEnabled").as_deref(),
+            Some("false")
+        );
+    }
+
+    #[test]
+    fn save_language_persists_code() {
+        let store = InMemoryConfigStore::new(Ini::new_cs());
+
+        save_language_with_store(&store, "fr");
+
+        let saved = store.load();
+        assert_eq!(saved.get("Discrakt", "language").as_deref(), Some("fr"));
+    }
+
+    #[test]
+    fn get_watch_stats_derives_end_date_from_runtime_when_expires_at_missing() {
+        let response = movie_watching("2024-01-01T00:00:00Z", None, Some(120));
+
+        let stats = get_watch_stats(&response);
+
+        assert_eq!(
+            stats.end_date,
+            DateTime::parse_from_rfc3339("2024-01-01T02:00:00Z").unwrap()
+        );
+    }
+
+    #[test]
+    fn get_watch_stats_does_not_panic_when_expires_at_and_runtime_are_both_missing() {
+        let response = movie_watching("2024-01-01T00:00:00Z", None, None);
+
+        let stats = get_watch_stats(&response);
+
+        assert_eq!(stats.end_date, stats.start_date);
+    }
+
+    #[test]
+    fn get_watch_stats_clamps_a_future_started_at_to_now() {
+        let far_future =
+            (Utc::now() + chrono::Duration::days(1)).to_rfc3339_opts(SecondsFormat::Secs, true);
+        let response = movie_watching(&far_future, None, Some(120));
+
+        let stats = get_watch_stats(&response);
+
+        assert!((stats.start_date.timestamp() - Utc::now().timestamp()).abs() < 5);
+    }
+
+    #[test]
+    fn get_watch_stats_prefers_trakts_reported_progress_over_the_derived_percentage() {
+        let mut response = movie_watching("2024-01-01T00:00:00Z", None, Some(120));
+        response.progress = Some(42.0);
+
+        let stats = get_watch_stats(&response);
+
+        assert_eq!(stats.percentage, Some(42.0));
+        assert_eq!(stats.watch_percentage.as_deref(), Some("42.00%"));
+    }
+
+    #[test]
+    fn get_watch_stats_leaves_percentage_none_instead_of_dividing_by_a_zero_window() {
+        let response = movie_watching("2024-01-01T00:00:00Z", Some("2024-01-01T00:00:00Z"), None);
+
+        let stats = get_watch_stats(&response);
+
+        assert_eq!(stats.percentage, None);
+        assert_eq!(stats.watch_percentage, None);
+    }
+
+    #[test]
+    fn is_below_min_progress_gates_when_percentage_is_below_the_threshold() {
+        assert!(is_below_min_progress(Some(5.0), 10.0));
+    }
+
+    #[test]
+    fn is_below_min_progress_does_not_gate_when_percentage_meets_the_threshold() {
+        assert!(!is_below_min_progress(Some(10.0), 10.0));
+    }
+
+    #[test]
+    fn is_below_min_progress_does_not_gate_a_checkin_with_no_measurable_progress() {
+        assert!(!is_below_min_progress(None, 10.0));
+    }
+
+    #[test]
+    fn format_rating_uses_a_comma_for_french_locales() {
+        assert_eq!(format_rating(8.5, "fr-FR"), "8,5");
+    }
+
+    #[test]
+    fn format_rating_uses_a_dot_for_english_locales() {
+        assert_eq!(format_rating(8.5, "en-US"), "8.5");
+    }
+
+    #[test]
+    fn format_rating_defaults_to_a_dot_for_unrecognized_languages() {
+        assert_eq!(format_rating(8.5, "xx-XX"), "8.5");
+    }
+
+    /// A minimal `credentials.ini` covering only the keys `parse_env` requires
+    /// unconditionally, so each test below only has to set the key it cares about.
+    fn minimal_ini() -> Ini {
+        let mut config = Ini::new_cs();
+        config.setstr("Trakt API", "traktUser", Some("someone"));
+        config.setstr("Trakt API", "traktClientID", Some("client-id"));
+        config.setstr("Trakt API", "enabledOAuth", Some("false"));
+        config
+    }
+
+    #[test]
+    fn parse_env_defaults_tls_ca_file_to_none_when_unconfigured() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.tls_ca_file, None);
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_tls_ca_file() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "tlsCaFile", Some("/etc/ssl/custom-ca.pem"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.tls_ca_file.as_deref(), Some("/etc/ssl/custom-ca.pem"));
+    }
+
+    #[test]
+    fn parse_env_defaults_trakt_extended_to_full() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.trakt_extended, "full");
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_trakt_extended_level() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "traktExtended", Some("metadata"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.trakt_extended, "metadata");
+    }
+
+    #[test]
+    fn parse_env_defaults_anonymize_logs_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.anonymize_logs);
+    }
+
+    #[test]
+    fn parse_env_reads_anonymize_logs_when_enabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "anonymizeLogs", Some("true"));
+
+        let env = parse_env(&config);
+
+        assert!(env.anonymize_logs);
+    }
+
+    #[test]
+    fn parse_env_defaults_show_my_rating_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.show_my_rating);
+    }
+
+    #[test]
+    fn parse_env_reads_show_my_rating_when_enabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "showMyRating", Some("true"));
+
+        let env = parse_env(&config);
+
+        assert!(env.show_my_rating);
+    }
+
+    #[test]
+    fn parse_env_defaults_show_studio_logo_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.show_studio_logo);
+    }
+
+    #[test]
+    fn parse_env_reads_show_studio_logo_when_enabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "showStudioLogo", Some("true"));
+
+        let env = parse_env(&config);
+
+        assert!(env.show_studio_logo);
+    }
+
+    #[test]
+    fn parse_env_defaults_linger_after_end_to_zero() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.linger_after_end, 0);
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_linger_after_end() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "lingerAfterEnd", Some("30"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.linger_after_end, 30);
+    }
+
+    #[test]
+    fn parse_env_defaults_scrobble_stop_grace_to_twenty_seconds() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.scrobble_stop_grace, 20);
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_scrobble_stop_grace() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "scrobbleStopGrace", Some("45"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.scrobble_stop_grace, 45);
+    }
+
+    #[test]
+    fn parse_env_defaults_small_image_and_text_to_trakt_branding() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.small_image, "trakt");
+        assert_eq!(env.small_text, "Discrakt");
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_small_image_and_text() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "smallImage", Some("custom-key"));
+        config.setstr("Discrakt", "smallText", Some("Watching now"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.small_image, "custom-key");
+        assert_eq!(env.small_text, "Watching now");
+    }
+
+    #[test]
+    fn parse_env_defaults_auto_refresh_tokens_to_true() {
+        let env = parse_env(&minimal_ini());
+        assert!(env.auto_refresh_tokens);
+    }
+
+    #[test]
+    fn parse_env_reads_auto_refresh_tokens_when_disabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "autoRefreshTokens", Some("false"));
+
+        let env = parse_env(&config);
+
+        assert!(!env.auto_refresh_tokens);
+    }
+
+    #[test]
+    fn parse_env_defaults_private_titles_to_empty() {
+        let env = parse_env(&minimal_ini());
+        assert!(env.private_titles.is_empty());
+    }
+
+    #[test]
+    fn parse_env_splits_and_trims_a_configured_private_titles_list() {
+        let mut config = minimal_ini();
+        config.setstr(
+            "Discrakt",
+            "privateTitles",
+            Some("Secret Show, , Another Title "),
+        );
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.private_titles, vec!["Secret Show", "Another Title"]);
+    }
+
+    #[test]
+    fn parse_env_defaults_additional_users_to_empty() {
+        let env = parse_env(&minimal_ini());
+        assert!(env.additional_users.is_empty());
+    }
+
+    #[test]
+    fn parse_env_splits_and_trims_a_configured_additional_users_list() {
+        let mut config = minimal_ini();
+        config.setstr("Trakt API", "additionalUsers", Some("alice, , bob "));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.additional_users, vec!["alice", "bob"]);
+    }
+
+    #[test]
+    fn next_rotation_user_is_none_for_a_single_user() {
+        assert_eq!(next_rotation_user(&["alice".to_string()], 5), None);
+    }
+
+    #[test]
+    fn next_rotation_user_cycles_through_multiple_users() {
+        let users = vec!["alice".to_string(), "bob".to_string()];
+        assert_eq!(next_rotation_user(&users, 0), Some("alice".to_string()));
+        assert_eq!(next_rotation_user(&users, 1), Some("bob".to_string()));
+        assert_eq!(next_rotation_user(&users, 2), Some("alice".to_string()));
+    }
+
+    #[test]
+    fn parse_env_defaults_imdb_and_trakt_link_bases() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.imdb_link_base, "https://www.imdb.com");
+        assert_eq!(env.trakt_link_base, "https://trakt.tv");
+    }
+
+    #[test]
+    fn parse_env_trims_trailing_slashes_from_configured_link_bases() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "imdbBase", Some("https://imdb.mirror/"));
+        config.setstr("Discrakt", "traktBase", Some("https://trakt.mirror/"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.imdb_link_base, "https://imdb.mirror");
+        assert_eq!(env.trakt_link_base, "https://trakt.mirror");
+    }
+
+    #[test]
+    fn parse_env_defaults_show_year_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.show_year);
+    }
+
+    #[test]
+    fn parse_env_reads_show_year_when_enabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "showYear", Some("true"));
+
+        let env = parse_env(&config);
+
+        assert!(env.show_year);
+    }
+
+    #[test]
+    fn parse_env_defaults_use_keyring_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.use_keyring);
+    }
+
+    #[test]
+    fn parse_env_reads_use_keyring_when_enabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "useKeyring", Some("true"));
+
+        let env = parse_env(&config);
+
+        assert!(env.use_keyring);
+    }
+
+    #[test]
+    fn presence_line_order_from_config_defaults_to_title_first() {
+        assert!(PresenceLineOrder::from_config(None) == PresenceLineOrder::TitleFirst);
+        assert!(
+            PresenceLineOrder::from_config(Some("bogus".to_string()))
+                == PresenceLineOrder::TitleFirst
+        );
+    }
+
+    #[test]
+    fn presence_line_order_from_config_reads_detail_first() {
+        assert!(
+            PresenceLineOrder::from_config(Some("detail-first".to_string()))
+                == PresenceLineOrder::DetailFirst
+        );
+    }
+
+    #[test]
+    fn artwork_preference_from_config_defaults_to_season() {
+        assert!(ArtworkPreference::from_config(None) == ArtworkPreference::Season);
+        assert!(
+            ArtworkPreference::from_config(Some("bogus".to_string())) == ArtworkPreference::Season
+        );
+    }
+
+    #[test]
+    fn artwork_preference_from_config_reads_show_and_still() {
+        assert!(
+            ArtworkPreference::from_config(Some("show".to_string())) == ArtworkPreference::Show
+        );
+        assert!(
+            ArtworkPreference::from_config(Some("still".to_string())) == ArtworkPreference::Still
+        );
+    }
+
+    #[test]
+    fn activity_kind_from_config_defaults_to_watching() {
+        assert!(ActivityKind::from_config(None) == ActivityKind::Watching);
+        assert!(ActivityKind::from_config(Some("bogus".to_string())) == ActivityKind::Watching);
+    }
+
+    #[test]
+    fn activity_kind_from_config_reads_listening() {
+        assert!(
+            ActivityKind::from_config(Some("listening".to_string())) == ActivityKind::Listening
+        );
+    }
+
+    #[test]
+    fn media_type_display_and_debug() {
+        assert_eq!(MediaType::Show.to_string(), "episode");
+        assert_eq!(MediaType::Movie.to_string(), "movie");
+        assert_eq!(format!("{:?}", MediaType::Movie), "Movie");
+    }
+
+    #[test]
+    fn parse_env_defaults_reconnect_every_n_updates_to_none() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.reconnect_every_n_updates, None);
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_reconnect_every_n_updates() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "reconnectEveryNUpdates", Some("50"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.reconnect_every_n_updates, Some(50));
+    }
+
+    #[test]
+    fn parse_env_defaults_recently_finished_minutes_to_none() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.recently_finished_minutes, None);
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_recently_finished_minutes() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "recentlyFinishedMinutes", Some("15"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.recently_finished_minutes, Some(15));
+    }
+
+    #[test]
+    fn config_dir_path_given_prefers_the_config_dir() {
+        let path = config_dir_path_given(
+            Some(PathBuf::from("/config")),
+            Some(PathBuf::from("/home/someone")),
+            || panic!("should not fall back to the exe dir"),
+        );
+        assert_eq!(path, PathBuf::from("/config/discrakt"));
+    }
+
+    #[test]
+    fn config_dir_path_given_falls_back_to_home_when_config_dir_is_unavailable() {
+        let path = config_dir_path_given(None, Some(PathBuf::from("/home/someone")), || {
+            panic!("should not fall back to the exe dir")
+        });
+        assert_eq!(path, PathBuf::from("/home/someone/.config/discrakt"));
+    }
+
+    #[test]
+    fn config_dir_path_given_falls_back_to_the_exe_dir_as_a_last_resort() {
+        let path = config_dir_path_given(None, None, || PathBuf::from("/opt/discrakt"));
+        assert_eq!(path, PathBuf::from("/opt/discrakt"));
+    }
+
+    #[test]
+    fn is_sleep_wake_gap_is_false_within_the_threshold() {
+        assert!(!is_sleep_wake_gap(
+            Duration::from_secs(30),
+            Duration::from_secs(60)
+        ));
+    }
+
+    #[test]
+    fn is_sleep_wake_gap_is_true_beyond_the_threshold() {
+        assert!(is_sleep_wake_gap(
+            Duration::from_secs(90),
+            Duration::from_secs(60)
+        ));
+    }
+
+    #[test]
+    fn parse_env_defaults_sleep_wake_gap_threshold_to_sixty_seconds() {
+        let env = parse_env(&minimal_ini());
+        assert_eq!(env.sleep_wake_gap_threshold, 60);
+    }
+
+    #[test]
+    fn parse_env_reads_a_configured_sleep_wake_gap_threshold() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "sleepWakeGapThreshold", Some("120"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.sleep_wake_gap_threshold, 120);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn is_headless_given_is_true_with_no_display_env_vars() {
+        assert!(is_headless_given(None, None));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn is_headless_given_is_false_when_either_display_var_is_set() {
+        assert!(!is_headless_given(Some(":0".to_string()), None));
+        assert!(!is_headless_given(None, Some("wayland-0".to_string())));
+    }
+
+    #[test]
+    fn find_config_file_uses_the_override_once_set() {
+        let path = PathBuf::from("/tmp/discrakt-test-credentials.ini");
+        set_config_path_override(path.clone());
+
+        assert_eq!(find_config_file(), Some(path));
+    }
+
+    #[test]
+    fn parse_env_defaults_show_pause_indicator_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.show_pause_indicator);
+    }
+
+    #[test]
+    fn parse_env_reads_show_pause_indicator_when_enabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "showPauseIndicator", Some("true"));
+
+        let env = parse_env(&config);
+
+        assert!(env.show_pause_indicator);
+    }
+
+    #[test]
+    fn is_playback_stalled_is_false_with_no_prior_progress() {
+        assert!(!is_playback_stalled(
+            &None,
+            "2024-01-01T00:00:00Z",
+            Some(0.5)
+        ));
+    }
+
+    #[test]
+    fn is_playback_stalled_is_false_when_the_percentage_advances() {
+        let last_progress = Some(("2024-01-01T00:00:00Z".to_string(), Some(0.5)));
+        assert!(!is_playback_stalled(
+            &last_progress,
+            "2024-01-01T00:00:00Z",
+            Some(0.6)
+        ));
+    }
+
+    #[test]
+    fn is_playback_stalled_is_false_when_the_title_changes() {
+        let last_progress = Some(("2024-01-01T00:00:00Z".to_string(), Some(0.5)));
+        assert!(!is_playback_stalled(
+            &last_progress,
+            "2024-01-02T00:00:00Z",
+            Some(0.5)
+        ));
+    }
+
+    #[test]
+    fn is_playback_stalled_is_true_when_the_same_title_reports_the_same_percentage() {
+        let last_progress = Some(("2024-01-01T00:00:00Z".to_string(), Some(0.5)));
+        assert!(is_playback_stalled(
+            &last_progress,
+            "2024-01-01T00:00:00Z",
+            Some(0.5)
+        ));
+    }
+
+    #[test]
+    fn parse_env_defaults_asset_key_slug_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.asset_key_slug);
+    }
+
+    #[test]
+    fn parse_env_enables_asset_key_slug_when_configured() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "assetKey", Some("slug"));
+
+        let env = parse_env(&config);
+
+        assert!(env.asset_key_slug);
+    }
+
+    #[test]
+    fn parse_env_defaults_show_certification_to_false_and_region_to_us() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.show_certification);
+        assert_eq!(env.certification_region, "US");
+    }
+
+    #[test]
+    fn parse_env_reads_show_certification_and_a_configured_region() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "showCertification", Some("true"));
+        config.setstr("Discrakt", "certificationRegion", Some("GB"));
+
+        let env = parse_env(&config);
+
+        assert!(env.show_certification);
+        assert_eq!(env.certification_region, "GB");
+    }
+
+    #[test]
+    fn is_quiet_reflects_the_value_set_quiet_was_called_with() {
+        assert!(!is_quiet());
+        set_quiet(true);
+        assert!(is_quiet());
+    }
+
+    #[test]
+    fn log_file_unavailable_message_includes_the_path_and_error() {
+        let path = PathBuf::from("/read-only/discrakt.log");
+        let e = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+
+        let message = log_file_unavailable_message(&path, &e);
+
+        assert!(message.contains("/read-only/discrakt.log"));
+        assert!(message.contains("denied"));
+        assert!(message.contains("console-only logging"));
+    }
+
+    #[test]
+    fn warn_log_file_unavailable_marks_itself_warned_after_the_first_call() {
+        let e = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        LOG_FILE_UNAVAILABLE_WARNED.store(false, Ordering::SeqCst);
+
+        warn_log_file_unavailable(&e);
+
+        assert!(LOG_FILE_UNAVAILABLE_WARNED.load(Ordering::SeqCst));
+        // A second call is a no-op past the swap; exercised for the side
+        // effect (no stderr write) rather than an assertable return value.
+        warn_log_file_unavailable(&e);
+    }
+
+    #[test]
+    fn parse_env_prefers_the_current_discord_client_id_key() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "discordClientId", Some("current"));
+        config.setstr("Discord", "discordClientID", Some("legacy"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.discord_client_id, "current");
+    }
+
+    #[test]
+    fn parse_env_falls_back_to_the_legacy_discord_client_id_key() {
+        let mut config = minimal_ini();
+        config.setstr("Discord", "discordClientID", Some("legacy"));
+
+        let env = parse_env(&config);
+
+        assert_eq!(env.discord_client_id, "legacy");
+    }
+
+    #[test]
+    fn parse_env_defaults_binge_hint_to_false() {
+        let env = parse_env(&minimal_ini());
+        assert!(!env.binge_hint);
+    }
+
+    #[test]
+    fn parse_env_reads_binge_hint_when_enabled() {
+        let mut config = minimal_ini();
+        config.setstr("Discrakt", "bingeHint", Some("true"));
+
+        let env = parse_env(&config);
+
+        assert!(env.binge_hint);
+    }
+}